@@ -0,0 +1,95 @@
+//! Stepping speed of large grids, the table-driven update being meant for
+//! 512x512 grids at thousands of steps per second, and build time of the
+//! largest outcome tables, rebuilt whenever the rule sets change.
+//!
+//! ```bash
+//! cargo bench --bench grid
+//! ```
+
+use rand::SeedableRng;
+use std::time::Instant;
+use toom_war::game::GameRng;
+use toom_war::grid::Grid;
+use toom_war::grid::neighbourhood::Neighbourhood;
+use toom_war::grid::table::OutcomeTable;
+use toom_war::{CellState, Rule};
+
+/// Steps per second of 512x512 grids below which the bench fails
+const MIN_STEPS_PER_SECOND: f64 = 1000.0;
+const STEPS: usize = 200;
+/// Build time of a Moore table between 4 players above which the bench fails
+const MAX_TABLE_MILLIS: f64 = 100.0;
+/// Rules of each player in the Moore table
+const TABLE_RULES: usize = 16;
+
+fn main() {
+    let mut rng = GameRng::seed_from_u64(0);
+    let rules: Vec<Vec<Rule>> = (0..2)
+        .map(|_| {
+            (0..8)
+                .map(|_| Rule::new(2, Neighbourhood::VonNeumann, &mut rng))
+                .collect()
+        })
+        .collect();
+    let rules: Vec<&[Rule]> = rules.iter().map(Vec::as_slice).collect();
+
+    let mut failed = false;
+    for (size, threads) in [(512, 1), (512, 0), (1024, 0)] {
+        let mut grid = Grid::new(size, &[0.2, 0.2], &mut rng);
+        let start = Instant::now();
+        for _ in 0..STEPS {
+            if !grid.next_parallel(&rules, threads) {
+                // Stable grid, nudge it so the following steps still have work to do
+                grid.values.set(0, CellState::Player1);
+            }
+        }
+        let per_second = STEPS as f64 / start.elapsed().as_secs_f64();
+        let threads = match threads {
+            0 => "auto".to_string(),
+            n => n.to_string(),
+        };
+        println!(
+            "{}x{}, {} threads: {:.0} steps/s, {:.0} Mcells/s",
+            size,
+            size,
+            threads,
+            per_second,
+            per_second * (size * size) as f64 / 1e6
+        );
+        failed |= size == 512 && per_second < MIN_STEPS_PER_SECOND;
+    }
+    if failed {
+        eprintln!(
+            "512x512 grids step slower than {} steps/s",
+            MIN_STEPS_PER_SECOND
+        );
+    }
+
+    // 5^8 patterns, with wildcard rules matching many of them
+    let rules: Vec<Vec<Rule>> = (0..4)
+        .map(|_| {
+            let mut rules: Vec<Rule> = (0..TABLE_RULES - 2)
+                .map(|_| Rule::new(4, Neighbourhood::Moore, &mut rng))
+                .collect();
+            rules.push("m*o**!2+*/rm".parse().unwrap());
+            rules.push("m>=3 o<=2".parse().unwrap());
+            rules
+        })
+        .collect();
+    let rules: Vec<&[Rule]> = rules.iter().map(Vec::as_slice).collect();
+    let start = Instant::now();
+    OutcomeTable::new(&rules, Neighbourhood::Moore);
+    let millis = start.elapsed().as_secs_f64() * 1000.0;
+    println!(
+        "Moore table, 4 players of {} rules: {:.0} ms",
+        TABLE_RULES, millis
+    );
+    if millis > MAX_TABLE_MILLIS {
+        eprintln!("Moore tables take longer than {} ms", MAX_TABLE_MILLIS);
+        failed = true;
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
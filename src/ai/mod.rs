@@ -0,0 +1,553 @@
+use crate::config::GameConfig;
+use crate::constants::{AI_LOOKAHEAD_WIDTH, AI_RANDOM_FINISH_PROBA, AI_SIMULATIONS};
+use crate::engine::{GameEngine, Phase};
+use crate::game::{Game, GameRng, cycle_period};
+use crate::grid::Grid;
+use crate::grid::table::{self, OutcomeTable};
+use crate::player::{Player, two_mut};
+use crate::rule::Rule;
+use crate::shop::{Shop, ShopError};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::thread::JoinHandle;
+
+/// How hard the computer player tries.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Difficulty {
+    /// Picks and buys at random.
+    Random,
+    /// Takes the single choice with the best simulated outcome.
+    Greedy,
+    /// Searches two moves ahead among the most promising choices.
+    LookAhead,
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Random => write!(f, "Random"),
+            Difficulty::Greedy => write!(f, "Greedy"),
+            Difficulty::LookAhead => write!(f, "Look-ahead"),
+        }
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(Difficulty::Random),
+            "greedy" => Ok(Difficulty::Greedy),
+            "lookahead" | "look-ahead" => Ok(Difficulty::LookAhead),
+            _ => Err(format!("unknown difficulty: {}", s)),
+        }
+    }
+}
+
+/// Operation a bot can perform during its shopping turn.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ShopAction {
+    BuyRule(usize),
+    DeleteRule(usize),
+    /// Delete rule `index` of player `opponent`
+    DeleteOpponentRule {
+        opponent: u8,
+        index: usize,
+    },
+    BuySpawn,
+}
+
+/// Decision of a bot for its turn, taken on a copy of the engine and played afterwards.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Decision {
+    /// Index of the starting rule to pick
+    Pick(usize),
+    /// Shop operations to perform in order, before ending the shopping turn
+    Shop(Vec<ShopAction>),
+}
+
+/// What shop operations change: the players and the shop stock. \
+/// Shop candidates are tried on copies of it rather than of the whole game.
+#[derive(Clone)]
+struct Position {
+    players: Vec<Player>,
+    shop: Shop,
+}
+
+/// Computer player. \
+/// Plays through the same `GameEngine` operations as a human, and evaluates
+/// choices by simulating battles between the candidate rule sets.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bot {
+    pub player: u8,
+    pub difficulty: Difficulty,
+    rng: GameRng,
+}
+
+impl Bot {
+    pub fn new(player: u8, difficulty: Difficulty, seed: u64) -> Bot {
+        Bot {
+            player,
+            difficulty,
+            rng: GameRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Whether the bot has a starting rule to pick or a shopping turn to play.
+    pub fn has_turn(&self, engine: &GameEngine) -> bool {
+        match engine.phase {
+            Phase::RulePicking => engine.picking_player() == self.player,
+            Phase::Shopping => engine.game.shop.current_player() == Some(self.player),
+            _ => false,
+        }
+    }
+
+    /// Decide the bot's turn, without changing `engine`. \
+    /// Returns `None` if it is not the bot's turn or it has nothing to pick.
+    pub fn decide(&mut self, engine: &GameEngine) -> Option<Decision> {
+        if !self.has_turn(engine) {
+            return None;
+        }
+        match engine.phase {
+            Phase::RulePicking => self.decide_pick(engine).map(Decision::Pick),
+            _ => Some(Decision::Shop(self.decide_shop(&engine.game))),
+        }
+    }
+
+    /// Play a decision taken by `decide`. \
+    /// Returns `true` if the rule was picked or the shopping turn ended.
+    pub fn play(&self, engine: &mut GameEngine, decision: Decision) -> bool {
+        match decision {
+            Decision::Pick(index) => engine.pick_rule(index),
+            Decision::Shop(actions) => {
+                if engine.game.shop.current_player() != Some(self.player) {
+                    return false;
+                }
+                for action in actions {
+                    let _ = apply(&mut engine.game, self.player, action);
+                }
+                engine.end_shop_turn();
+                true
+            }
+        }
+    }
+
+    /// Pick a starting rule if it is the bot's turn. \
+    /// Returns `true` if a rule was picked.
+    pub fn pick_rule(&mut self, engine: &mut GameEngine) -> bool {
+        if engine.phase != Phase::RulePicking {
+            return false;
+        }
+        match self.decide(engine) {
+            Some(decision) => self.play(engine, decision),
+            None => false,
+        }
+    }
+
+    /// Place the bot's starting cells, at random, if it is its turn to deploy. \
+    /// Returns `true` if the bot deployed.
+    pub fn deploy(&mut self, engine: &mut GameEngine) -> bool {
+        if engine.deploying_player() != Some(self.player) {
+            return false;
+        }
+        engine.deploy_at_random();
+        true
+    }
+
+    /// Play the bot's whole shopping turn if it is its turn, then end it. \
+    /// Returns `true` if the bot shopped.
+    pub fn shop(&mut self, engine: &mut GameEngine) -> bool {
+        if engine.phase != Phase::Shopping {
+            return false;
+        }
+        match self.decide(engine) {
+            Some(decision) => self.play(engine, decision),
+            None => false,
+        }
+    }
+
+    /// Starting rule to pick among the available ones, `None` if there is none.
+    fn decide_pick(&mut self, engine: &GameEngine) -> Option<usize> {
+        let available: Vec<usize> = (0..engine.rule_picker.rules.len())
+            .filter(|i| engine.rule_picker.rules_available[*i])
+            .collect();
+        if available.is_empty() {
+            return None;
+        }
+
+        let index = match self.difficulty {
+            Difficulty::Random => *available.choose(&mut self.rng).unwrap(),
+            Difficulty::Greedy => {
+                let seeds = self.seeds();
+                self.rank_picks(engine, &available, &seeds)[0].0
+            }
+            Difficulty::LookAhead => {
+                // Keep the pick whose worst case, after the best answer of any player
+                // of another side, is the best
+                let seeds = self.seeds();
+                let ranked = self.rank_picks(engine, &available, &seeds);
+                let game = &engine.game;
+                let opponents: Vec<u8> =
+                    opponents(&game.config, game.players.len(), self.player).collect();
+                let mut best = (ranked[0].0, f32::MIN);
+                for &(pick, _) in ranked.iter().take(AI_LOOKAHEAD_WIDTH) {
+                    let mut worst = f32::MAX;
+                    for &answer in available.iter().filter(|i| **i != pick) {
+                        for &opponent in &opponents {
+                            let mut players = game.players.clone();
+                            let rule = engine.rule_picker.rules[pick].clone();
+                            player_mut(&mut players, self.player).rules.push(rule);
+                            let rule = engine.rule_picker.rules[answer].clone();
+                            player_mut(&mut players, opponent).rules.push(rule);
+                            let score = evaluate(&game.config, &players, self.player, &seeds);
+                            worst = worst.min(score);
+                        }
+                    }
+                    if worst > best.1 {
+                        best = (pick, worst);
+                    }
+                }
+                best.0
+            }
+        };
+        Some(index)
+    }
+
+    /// Shop operations of the bot's turn, tried in order on a copy of the players and shop.
+    fn decide_shop(&mut self, game: &Game) -> Vec<ShopAction> {
+        let config = &game.config;
+        let mut position = Position {
+            players: game.players.clone(),
+            shop: game.shop.clone(),
+        };
+        let mut decided = Vec::new();
+        loop {
+            let actions = position.valid_actions(self.player);
+            if actions.is_empty() {
+                break;
+            }
+
+            let action = match self.difficulty {
+                Difficulty::Random => {
+                    if self.rng.gen_bool(AI_RANDOM_FINISH_PROBA) {
+                        None
+                    } else {
+                        actions.choose(&mut self.rng).copied()
+                    }
+                }
+                Difficulty::Greedy => {
+                    let seeds = self.seeds();
+                    let base = evaluate(config, &position.players, self.player, &seeds);
+                    let ranked = self.rank_actions(config, &position, &actions, &seeds);
+                    ranked
+                        .first()
+                        .filter(|(_, score)| *score > base)
+                        .map(|(action, _)| *action)
+                }
+                Difficulty::LookAhead => {
+                    // Value each promising action by the best follow-up it allows
+                    let seeds = self.seeds();
+                    let base = evaluate(config, &position.players, self.player, &seeds);
+                    let ranked = self.rank_actions(config, &position, &actions, &seeds);
+                    let mut best: Option<(ShopAction, f32)> = None;
+                    for &(first, score) in ranked.iter().take(AI_LOOKAHEAD_WIDTH) {
+                        let mut value = score;
+                        let mut after = position.clone();
+                        let _ = after.apply(self.player, first);
+                        for &(second, _) in ranked.iter().take(AI_LOOKAHEAD_WIDTH) {
+                            let mut next = after.clone();
+                            if second != first && next.apply(self.player, second).is_ok() {
+                                let score = evaluate(config, &next.players, self.player, &seeds);
+                                value = value.max(score);
+                            }
+                        }
+                        if best.is_none_or(|(_, v)| value > v) {
+                            best = Some((first, value));
+                        }
+                    }
+                    best.filter(|(_, value)| *value > base)
+                        .map(|(action, _)| action)
+                }
+            };
+
+            match action {
+                Some(action) => {
+                    let _ = position.apply(self.player, action);
+                    decided.push(action);
+                }
+                None => break,
+            }
+        }
+        decided
+    }
+
+    /// Grid seeds shared by every candidate of a decision, so they are compared on the same battles.
+    fn seeds(&mut self) -> Vec<u64> {
+        (0..AI_SIMULATIONS).map(|_| self.rng.r#gen()).collect()
+    }
+
+    /// Available picks sorted from best to worst simulated outcome.
+    fn rank_picks(
+        &self,
+        engine: &GameEngine,
+        available: &[usize],
+        seeds: &[u64],
+    ) -> Vec<(usize, f32)> {
+        let game = &engine.game;
+        let mut ranked: Vec<(usize, f32)> = available
+            .iter()
+            .map(|&i| {
+                let mut players = game.players.clone();
+                let rule = engine.rule_picker.rules[i].clone();
+                player_mut(&mut players, self.player).rules.push(rule);
+                (i, evaluate(&game.config, &players, self.player, seeds))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+
+    /// Shop actions sorted from best to worst simulated outcome.
+    fn rank_actions(
+        &self,
+        config: &GameConfig,
+        position: &Position,
+        actions: &[ShopAction],
+        seeds: &[u64],
+    ) -> Vec<(ShopAction, f32)> {
+        let mut ranked: Vec<(ShopAction, f32)> = actions
+            .iter()
+            .map(|&action| {
+                let mut next = position.clone();
+                let _ = next.apply(self.player, action);
+                (action, evaluate(config, &next.players, self.player, seeds))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+/// Bot taking its decision on a worker thread, so the window keeps drawing meanwhile.
+pub struct Thinking {
+    pub player: u8,
+    handle: JoinHandle<(Bot, Option<Decision>)>,
+}
+
+impl Thinking {
+    /// Start `bot` deciding its turn on a copy of `engine`.
+    pub fn start(bot: &Bot, engine: &GameEngine) -> Thinking {
+        let mut bot = bot.clone();
+        let engine = engine.clone();
+        Thinking {
+            player: bot.player,
+            handle: std::thread::spawn(move || {
+                let decision = bot.decide(&engine);
+                (bot, decision)
+            }),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait for the decision, along with the bot whose random state advanced while deciding.
+    pub fn join(self) -> (Bot, Option<Decision>) {
+        self.handle.join().expect("bot thread panicked")
+    }
+}
+
+impl Position {
+    fn apply(&mut self, player: u8, action: ShopAction) -> Result<(), ShopError> {
+        apply_to(&mut self.shop, &mut self.players, player, action)
+    }
+
+    /// Every shop operation `player` can currently afford.
+    fn valid_actions(&self, player: u8) -> Vec<ShopAction> {
+        let me = &self.players[player as usize - 1];
+        let candidates = (0..self.shop.rules.len())
+            .map(ShopAction::BuyRule)
+            .chain((0..me.rules.len()).map(ShopAction::DeleteRule))
+            .chain(
+                opponents(&self.shop.config, self.players.len(), player).flat_map(|opponent| {
+                    (0..self.players[opponent as usize - 1].rules.len())
+                        .map(move |index| ShopAction::DeleteOpponentRule { opponent, index })
+                }),
+            )
+            .chain(std::iter::once(ShopAction::BuySpawn));
+
+        candidates
+            .filter(|action| self.clone().apply(player, *action).is_ok())
+            .collect()
+    }
+}
+
+fn player_mut(players: &mut [Player], player: u8) -> &mut Player {
+    &mut players[player as usize - 1]
+}
+
+/// Players among the first `nb_players` on another side than `player`.
+fn opponents(config: &GameConfig, nb_players: usize, player: u8) -> impl Iterator<Item = u8> + '_ {
+    let side = config.side_of(player);
+    (1..=nb_players as u8).filter(move |p| config.side_of(*p) != side)
+}
+
+/// Apply a shop operation for `player` through the regular `Shop` methods.
+pub fn apply(game: &mut Game, player: u8, action: ShopAction) -> Result<(), ShopError> {
+    apply_to(&mut game.shop, &mut game.players, player, action)
+}
+
+fn apply_to(
+    shop: &mut Shop,
+    players: &mut [Player],
+    player: u8,
+    action: ShopAction,
+) -> Result<(), ShopError> {
+    let me = player as usize - 1;
+    match action {
+        ShopAction::BuyRule(i) => shop.buy_rule(&mut players[me], i),
+        ShopAction::DeleteRule(i) => shop.delete_rule(&mut players[me], i),
+        ShopAction::DeleteOpponentRule { opponent, index } => {
+            if !opponents(&shop.config, players.len(), player).any(|p| p == opponent) {
+                return Err(ShopError::InvalidIndex);
+            }
+            let (me, opponent) = two_mut(players, player, opponent);
+            shop.delete_opponent_rule(me, opponent, index)
+        }
+        ShopAction::BuySpawn => shop.buy_spawn(&mut players[me]),
+    }
+}
+
+/// Every shop operation `player` can currently afford.
+pub fn valid_actions(game: &Game, player: u8) -> Vec<ShopAction> {
+    Position {
+        players: game.players.clone(),
+        shop: game.shop.clone(),
+    }
+    .valid_actions(player)
+}
+
+/// Average cell lead of `player`'s side over the best other side,
+/// in battles between `players` played on the grids generated by `seeds`.
+pub fn evaluate(config: &GameConfig, players: &[Player], player: u8, seeds: &[u64]) -> f32 {
+    let side = config.side_of(player) as usize - 1;
+    let rules: Vec<&[Rule]> = players.iter().map(|p| p.rules.as_slice()).collect();
+    let mut table = OutcomeTable::with_conflict(&rules, config.neighbourhood, config.conflict);
+    let mut total = 0.0;
+    for &seed in seeds {
+        let counts = simulate(config, players, &mut table, seed);
+        let totals = config.side_totals(&counts);
+        let best_other = totals
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != side)
+            .map(|(_, t)| *t)
+            .max()
+            .unwrap_or(0);
+        total += totals[side] as f32 - best_other as f32;
+    }
+    total / seeds.len() as f32
+}
+
+/// Cells of each player once a round is over on the grid generated by `seed`,
+/// stepped with the outcome table of `players`' rules like `Game::step`.
+fn simulate(
+    config: &GameConfig,
+    players: &[Player],
+    table: &mut OutcomeTable,
+    seed: u64,
+) -> Vec<usize> {
+    let mut rng = GameRng::seed_from_u64(seed);
+    let spawn_probas: Vec<f32> = players.iter().map(|p| p.spawn_proba).collect();
+    let mut grid = Grid::new(config.grid_size, &spawn_probas, &mut rng)
+        .with_neighbourhood(config.neighbourhood);
+    let mut recent_grids = VecDeque::new();
+    cycle_period(&mut recent_grids, &grid.values, config.max_cycle_period);
+    for iteration in 0..config.max_iterations {
+        table.set_step(iteration, table::step_salt(seed, 1, iteration));
+        if !grid.next_with_table(table, config.threads)
+            || cycle_period(&mut recent_grids, &grid.values, config.max_cycle_period).is_some()
+        {
+            break;
+        }
+    }
+    grid.counts(players.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+
+    #[test]
+    fn test_random_bots_play_a_round() {
+        let mut engine = GameEngine::new_with_seed(GameConfig::default(), 11);
+        let mut bots = [
+            Bot::new(1, Difficulty::Random, 1),
+            Bot::new(2, Difficulty::Random, 2),
+        ];
+
+        while engine.phase == Phase::RulePicking {
+            assert!(bots.iter_mut().any(|bot| bot.pick_rule(&mut engine)));
+        }
+        engine.run_round();
+        engine.open_shop();
+        while engine.phase == Phase::Shopping {
+            assert!(bots.iter_mut().any(|bot| bot.shop(&mut engine)));
+        }
+        assert_eq!(engine.phase, Phase::Battle);
+    }
+
+    #[test]
+    fn test_bot_decides_on_worker_thread() {
+        let config = GameConfig {
+            grid_size: 16,
+            ..GameConfig::default()
+        };
+        let mut engine = GameEngine::new_with_seed(config, 5);
+        let bot = Bot::new(engine.picking_player(), Difficulty::Greedy, 3);
+        assert!(bot.has_turn(&engine));
+
+        let thinking = Thinking::start(&bot, &engine);
+        let (bot, decision) = thinking.join();
+        let Some(Decision::Pick(index)) = decision else {
+            panic!("expected a pick, got {:?}", decision);
+        };
+        let rule = engine.rule_picker.rules[index].clone();
+        assert!(bot.play(&mut engine, Decision::Pick(index)));
+        let rules = &engine.game.players[bot.player as usize - 1].rules;
+        assert_eq!(rules.last(), Some(&rule));
+        assert!(!bot.has_turn(&engine));
+    }
+
+    #[test]
+    fn test_look_ahead_answers_for_the_other_side() {
+        let config = GameConfig {
+            players: 4,
+            teams: true,
+            grid_size: 16,
+            ..GameConfig::default()
+        };
+        assert_eq!(opponents(&config, 4, 1).collect::<Vec<_>>(), [2, 4]);
+        assert_eq!(opponents(&config, 4, 2).collect::<Vec<_>>(), [1, 3]);
+
+        let mut engine = GameEngine::new_with_seed(config, 9);
+        let mut bots: Vec<Bot> = (1..=4)
+            .map(|player| Bot::new(player, Difficulty::LookAhead, player as u64))
+            .collect();
+        while engine.phase == Phase::RulePicking {
+            assert!(bots.iter_mut().any(|bot| bot.pick_rule(&mut engine)));
+        }
+    }
+
+    #[test]
+    fn test_difficulty_from_str() {
+        assert_eq!("greedy".parse(), Ok(Difficulty::Greedy));
+        assert_eq!("Look-ahead".parse(), Ok(Difficulty::LookAhead));
+        assert!("hard".parse::<Difficulty>().is_err());
+    }
+}
@@ -0,0 +1,415 @@
+//! Headless tournament runner. \
+//! Plays many seeded matches between two to four contestants and prints win rates,
+//! average cell counts and 95% confidence intervals, to help balance the
+//! shop and player settings of `GameConfig`.
+//!
+//! ```bash
+//! cargo run --release --bin tournament -- --p1 greedy --p2 random --matches 1000
+//! cargo run --release --bin tournament -- --p1 rules:1000,0100 --p2 random
+//! cargo run --release --bin tournament -- --p1 "rules:#27,m>=2 o<=1,1200/r" --p2 greedy
+//! cargo run --release --bin tournament -- --preset quick --set shop_price_rule=2
+//! cargo run --release --bin tournament -- --set players=4 --set teams=true --p1 greedy
+//! ```
+
+use toom_war::ai::{Bot, Difficulty};
+use toom_war::config::{GameConfig, VictoryCondition, leader};
+use toom_war::engine::{GameEngine, Phase};
+use toom_war::rule::atom::Atom;
+use toom_war::rule::pattern::PatternRule;
+use toom_war::{CellState, MAX_PLAYERS, Rule};
+
+use std::time::Instant;
+
+/// One side of the tournament.
+#[derive(Clone)]
+enum Contestant {
+    Bot(Difficulty),
+    /// Fixed rule set, never shopping. \
+    /// Written from the owner's point of view: 1 = own cells, 2 = opponent cells.
+    Fixed(Vec<Rule>),
+}
+
+impl std::fmt::Display for Contestant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Contestant::Bot(difficulty) => write!(f, "{} bot", difficulty),
+            Contestant::Fixed(rules) => write!(f, "{} fixed rules", rules.len()),
+        }
+    }
+}
+
+impl std::str::FromStr for Contestant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("rules:") {
+            Some(list) => list
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map(Contestant::Fixed),
+            None => s.parse().map(Contestant::Bot),
+        }
+    }
+}
+
+/// Swap Player 1 and `player` in a rule, so a fixed rule set plays the same for `player`.
+fn mirror(rule: &Rule, player: u8) -> Rule {
+    let swap_cell = |cell: CellState| match cell.owner() {
+        Some(1) => CellState::player(player),
+        Some(p) if p == player => CellState::Player1,
+        _ => cell,
+    };
+    let swap = |atom: Atom| match atom {
+        Atom::Cell(cell) => Atom::Cell(swap_cell(cell)),
+        Atom::Not(cell) => Atom::Not(swap_cell(cell)),
+        _ => atom,
+    };
+    match rule {
+        Rule::Pattern(pattern) => Rule::Pattern(PatternRule {
+            atoms: pattern.atoms.iter().map(|atom| swap(*atom)).collect(),
+            symmetry: pattern.symmetry,
+        }),
+        // Counts are relative to the owner already
+        Rule::Count(_) => rule.clone(),
+    }
+}
+
+/// Running mean and variance (Welford).
+#[derive(Default)]
+struct Stat {
+    n: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl Stat {
+    fn push(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Half-width of the 95% confidence interval of the mean.
+    fn ci95(&self) -> f64 {
+        if self.n < 2 {
+            return 0.0;
+        }
+        1.96 * (self.m2 / (self.n - 1) as f64).sqrt() / (self.n as f64).sqrt()
+    }
+}
+
+/// Statistics per player, wins counting for every member of the winning side.
+#[derive(Default)]
+struct Results {
+    match_wins: Vec<Stat>,
+    match_draws: Stat,
+    round_wins: Vec<Stat>,
+    cells: Vec<Stat>,
+    iterations: Stat,
+    rounds: Stat,
+}
+
+impl Results {
+    fn new(nb_players: usize) -> Results {
+        let stats = || (0..nb_players).map(|_| Stat::default()).collect();
+        Results {
+            match_wins: stats(),
+            round_wins: stats(),
+            cells: stats(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Safety net for matches that never reach their victory condition, e.g. endless draws.
+const MAX_ROUNDS_PER_MATCH: usize = 1000;
+
+/// Set up a match between `contestants` and play its rule picking, returning the engine
+/// and the bots of the contestants that are bots. \
+/// Fails if no contestant can make progress, e.g. a rule picking stuck on a player nobody controls.
+fn start_match(
+    config: &GameConfig,
+    seed: u64,
+    contestants: &[Contestant],
+) -> Result<(GameEngine, Vec<Bot>), String> {
+    let mut engine = GameEngine::new_with_seed(config.clone(), seed);
+    let mut bots: Vec<Bot> = Vec::new();
+    let nb_players = engine.game.players.len();
+    for (i, contestant) in contestants.iter().take(nb_players).enumerate() {
+        let player = i as u8 + 1;
+        match contestant {
+            Contestant::Bot(difficulty) => bots.push(Bot::new(
+                player,
+                *difficulty,
+                seed ^ ((player as u64) << 32),
+            )),
+            Contestant::Fixed(rules) => {
+                let rules = rules.iter().map(|rule| mirror(rule, player)).collect();
+                engine.set_rules(player, rules);
+            }
+        }
+    }
+
+    while engine.phase == Phase::RulePicking {
+        let picked = bots
+            .iter_mut()
+            .fold(false, |picked, bot| bot.pick_rule(&mut engine) | picked);
+        if !picked {
+            return Err(format!(
+                "seed {}: Player {} has no rules to pick from",
+                seed,
+                engine.picking_player()
+            ));
+        }
+    }
+    Ok((engine, bots))
+}
+
+/// Play one match and add it to `results`, see `start_match` for the errors.
+fn play_match(
+    config: &GameConfig,
+    seed: u64,
+    contestants: &[Contestant],
+    results: &mut Results,
+) -> Result<(), String> {
+    let (mut engine, mut bots) = start_match(config, seed, contestants)?;
+    let nb_players = engine.game.players.len();
+    for _ in 0..MAX_ROUNDS_PER_MATCH {
+        while engine.phase == Phase::Deployment {
+            let deployed = bots.iter_mut().any(|bot| bot.deploy(&mut engine));
+            if !deployed {
+                // Fixed rule sets scatter their cells
+                engine.deploy_at_random();
+            }
+        }
+        engine.run_round();
+        let counts = engine.game.grid.counts(nb_players);
+        let winner = leader(&config.side_totals(&counts));
+        for (i, count) in counts.iter().enumerate() {
+            let side = config.side_of(i as u8 + 1);
+            results.cells[i].push(*count as f64);
+            results.round_wins[i].push((winner == Some(side)) as u8 as f64);
+        }
+        results.iterations.push(engine.game.iteration as f64);
+
+        if engine.phase == Phase::MatchOver {
+            break;
+        }
+        engine.open_shop();
+        while engine.phase == Phase::Shopping {
+            let shopped = bots.iter_mut().any(|bot| bot.shop(&mut engine));
+            if !shopped {
+                // Fixed rule sets never shop
+                engine.end_shop_turn();
+            }
+        }
+    }
+
+    let winner = engine.game.match_winner();
+    for (i, stat) in results.match_wins.iter_mut().enumerate() {
+        let side = config.side_of(i as u8 + 1);
+        stat.push((winner == Some(side)) as u8 as f64);
+    }
+    results.match_draws.push(winner.is_none() as u8 as f64);
+    results.rounds.push(engine.game.rounds_played as f64);
+    Ok(())
+}
+
+fn percent(stat: &Stat) -> String {
+    format!("{:5.1}% ± {:4.1}%", stat.mean * 100.0, stat.ci95() * 100.0)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut matches = 1000;
+    let mut seed = 0;
+    let mut config = GameConfig::default();
+    let mut contestants = vec![Contestant::Bot(Difficulty::Random); MAX_PLAYERS];
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--matches" => matches = value()?.parse()?,
+            "--rounds" => config.victory = VictoryCondition::FixedRounds(value()?.parse()?),
+            "--seed" => seed = value()?.parse()?,
+            "--p1" => contestants[0] = value()?.parse()?,
+            "--p2" => contestants[1] = value()?.parse()?,
+            "--p3" => contestants[2] = value()?.parse()?,
+            "--p4" => contestants[3] = value()?.parse()?,
+            _ => {
+                if !config.parse_flag(&arg, &mut args)? {
+                    return Err(format!("unknown argument: {}", arg).into());
+                }
+            }
+        }
+    }
+
+    config.validate()?;
+
+    let nb_players = config.nb_players();
+    let names: Vec<String> = contestants[..nb_players]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    println!(
+        "{}: {} matches, {}{}, seeds {}..{}",
+        names.join(" vs "),
+        matches,
+        config.victory,
+        if config.is_teams() { ", 2v2" } else { "" },
+        seed,
+        seed + matches as u64
+    );
+
+    let start = Instant::now();
+    let mut results = Results::new(nb_players);
+    for i in 0..matches {
+        play_match(&config, seed + i as u64, &contestants, &mut results)?;
+    }
+    let elapsed = start.elapsed();
+
+    let row = |label: &str, cells: Vec<String>| {
+        let cells: String = cells.iter().map(|c| format!("{:<22}", c)).collect();
+        println!("{:<14}{}", label, cells);
+    };
+    println!();
+    row(
+        "",
+        (1..=nb_players).map(|i| format!("Player {}", i)).collect(),
+    );
+    row(
+        "Match wins",
+        results.match_wins.iter().map(percent).collect(),
+    );
+    println!("{:<14}{}", "Match draws", percent(&results.match_draws));
+    row(
+        "Round wins",
+        results.round_wins.iter().map(percent).collect(),
+    );
+    row(
+        "Avg cells",
+        results
+            .cells
+            .iter()
+            .map(|stat| format!("{:.1} ± {:.1}", stat.mean, stat.ci95()))
+            .collect(),
+    );
+    println!(
+        "{:<14}{:.1} ± {:.1}",
+        "Avg rounds",
+        results.rounds.mean,
+        results.rounds.ci95()
+    );
+    println!(
+        "{:<14}{:.1} ± {:.1}",
+        "Avg length",
+        results.iterations.mean,
+        results.iterations.ci95()
+    );
+    println!();
+    println!(
+        "{} matches in {:.2?} ({:.1} matches/s)",
+        matches,
+        elapsed,
+        matches as f64 / elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(list: &str) -> Vec<Rule> {
+        list.split(',').map(|rule| rule.parse().unwrap()).collect()
+    }
+
+    fn short_config() -> GameConfig {
+        GameConfig {
+            grid_size: 16,
+            victory: VictoryCondition::FixedRounds(2),
+            ..GameConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_short_fixed_rule_set_plays() {
+        let config = short_config();
+        let contestants = [
+            "rules:1000,0100".parse().unwrap(),
+            Contestant::Bot(Difficulty::Random),
+        ];
+        // The fixed set is played as is, the bot picks its own rules
+        let (engine, bots) = start_match(&config, 0, &contestants).unwrap();
+        assert_eq!(engine.phase, Phase::Battle);
+        assert_eq!(bots.len(), 1);
+        assert_eq!(engine.game.players[0].rules, rules("1000,0100"));
+        assert_eq!(
+            engine.game.players[1].rules.len(),
+            config.player_start_rules
+        );
+
+        let mut results = Results::new(2);
+        play_match(&config, 0, &contestants, &mut results).unwrap();
+        assert_eq!(results.rounds.n, 1);
+    }
+
+    #[test]
+    fn test_mirror_swaps_seats() {
+        let rule = |text: &str| text.parse::<Rule>().unwrap();
+        assert_eq!(mirror(&rule("1020"), 1), rule("1020"));
+        assert_eq!(mirror(&rule("1020"), 2), rule("2010"));
+        assert_eq!(mirror(&rule("1*3!1/r"), 3), rule("3*1!3/r"));
+        assert_eq!(mirror(&rule("m>=2 o<=1"), 2), rule("m>=2 o<=1"));
+
+        // The same fixed set seen from each seat
+        let contestant: Contestant = "rules:1000,0102".parse().unwrap();
+        let contestants = [contestant.clone(), contestant];
+        let (engine, _) = start_match(&short_config(), 0, &contestants).unwrap();
+        assert_eq!(engine.game.players[0].rules, rules("1000,0102"));
+        assert_eq!(engine.game.players[1].rules, rules("2000,0201"));
+    }
+
+    #[test]
+    fn test_mirrored_pair_tallies() {
+        // Each match and round is won by one seat or drawn, never both
+        let contestant: Contestant = "rules:1000,0100,0010,0001".parse().unwrap();
+        let contestants = [contestant.clone(), contestant];
+        let mut results = Results::new(2);
+        let matches = 6;
+        for seed in 0..matches {
+            play_match(&short_config(), seed, &contestants, &mut results).unwrap();
+        }
+        let total = |stat: &Stat| stat.mean * stat.n as f64;
+        assert_eq!(results.match_wins[0].n, matches as usize);
+        let outcomes = total(&results.match_wins[0])
+            + total(&results.match_wins[1])
+            + total(&results.match_draws);
+        assert!((outcomes - matches as f64).abs() < 1e-9);
+        let round_wins = total(&results.round_wins[0]) + total(&results.round_wins[1]);
+        assert!(round_wins <= results.round_wins[0].n as f64 + 1e-9);
+        assert_eq!(results.round_wins[0].n, 2 * matches as usize);
+    }
+
+    #[test]
+    fn test_stat_ci95() {
+        let stat = |sample: &[f64]| {
+            let mut stat = Stat::default();
+            sample.iter().for_each(|x| stat.push(*x));
+            stat
+        };
+        // A constant sample leaves no doubt about its mean
+        let constant = stat(&[3.0; 10]);
+        assert_eq!(constant.mean, 3.0);
+        assert_eq!(constant.ci95(), 0.0);
+        assert_eq!(stat(&[5.0]).ci95(), 0.0);
+        // Sample variance 4/3 over 4 values: 1.96 * sqrt(4/3) / sqrt(4)
+        let spread = stat(&[1.0, 1.0, 3.0, 3.0]);
+        let sd = (4.0f64 / 3.0).sqrt();
+        assert!((spread.mean - 2.0).abs() < 1e-12);
+        assert!((spread.ci95() - 1.96 * sd / 2.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,92 @@
+use eframe::egui;
+
+/// One line of a chart.
+pub struct Series {
+    pub name: String,
+    pub color: egui::Color32,
+    pub values: Vec<usize>,
+}
+
+/// Line chart of `series` over `size`, scaled to the largest value. \
+/// The first values are at x `first_x`, named `x_label` in the tooltip
+/// listing every series at the hovered x.
+pub fn show(
+    ui: &mut egui::Ui,
+    size: egui::Vec2,
+    title: &str,
+    x_label: &str,
+    first_x: usize,
+    series: &[Series],
+) {
+    ui.vertical(|ui| {
+        ui.label(egui::RichText::new(title).small());
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(250));
+        painter.rect_stroke(
+            rect,
+            2.0,
+            egui::Stroke::new(1.0, egui::Color32::from_gray(180)),
+            egui::StrokeKind::Inside,
+        );
+
+        let len = series.iter().map(|s| s.values.len()).max().unwrap_or(0);
+        if len == 0 {
+            return;
+        }
+        let max = series
+            .iter()
+            .flat_map(|s| &s.values)
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1) as f32;
+        let plot = rect.shrink(3.0);
+        let point = |i: usize, value: usize| {
+            let x = if len > 1 {
+                plot.left() + plot.width() * i as f32 / (len - 1) as f32
+            } else {
+                plot.center().x
+            };
+            egui::pos2(x, plot.bottom() - plot.height() * value as f32 / max)
+        };
+
+        for s in series {
+            let points: Vec<egui::Pos2> = s
+                .values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| point(i, *value))
+                .collect();
+            match points.as_slice() {
+                [single] => {
+                    painter.circle_filled(*single, 2.0, s.color);
+                }
+                _ => {
+                    painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, s.color)));
+                }
+            }
+        }
+
+        let Some(pos) = response.hover_pos() else {
+            return;
+        };
+        let i = match len {
+            1 => 0,
+            _ => (((pos.x - plot.left()) / plot.width() * (len - 1) as f32).round() as usize)
+                .min(len - 1),
+        };
+        painter.vline(
+            point(i, 0).x,
+            plot.y_range(),
+            egui::Stroke::new(1.0, egui::Color32::from_gray(120)),
+        );
+        let mut text = format!("{} {}", x_label, first_x + i);
+        for s in series {
+            if let Some(value) = s.values.get(i) {
+                text.push_str(&format!("\n{}: {}", s.name, value));
+            }
+        }
+        response.on_hover_text_at_pointer(text);
+    });
+}
@@ -1 +1,4 @@
+pub mod chart;
+pub mod rule_entry;
+pub mod score_bar;
 pub mod text;
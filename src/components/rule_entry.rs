@@ -0,0 +1,29 @@
+use crate::rule::Rule;
+use eframe::egui;
+
+/// Text box to type a rule in its displayed form, e.g. `1020`, `mo*!3/rm` or `m>=2 o<=1`,
+/// or as `#` and its index. \
+/// Shows the rule typed, or why it is invalid, and returns it once valid.
+pub fn show(ui: &mut egui::Ui, text: &mut String) -> Option<Rule> {
+    ui.add(
+        egui::TextEdit::singleline(text)
+            .hint_text("1020, mo*!3/rm, m>=2 o<=1, #33")
+            .desired_width(160.0),
+    );
+    if text.trim().is_empty() {
+        return None;
+    }
+    match text.parse::<Rule>() {
+        Ok(rule) => {
+            if let Some(index) = rule.index() {
+                ui.label(format!("#{}", index));
+            }
+            rule.show(ui);
+            Some(rule)
+        }
+        Err(error) => {
+            ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+            None
+        }
+    }
+}
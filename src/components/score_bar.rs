@@ -0,0 +1,37 @@
+use crate::constants::MAX_PLAYERS;
+use crate::grid::Grid;
+use crate::grid::cell::CellState;
+use eframe::egui;
+
+/// Bar showing the share of the grid owned by each player. \
+/// Odd players fill it from the left, even players from the right, neutral cells in between.
+pub fn show(ui: &mut egui::Ui, width: f32, grid: &Grid) {
+    let counts = grid.counts(MAX_PLAYERS);
+    let player = |p: u8| (counts[p as usize - 1], CellState::player(p).color());
+    let segments = [
+        player(1),
+        player(3),
+        (grid.count(CellState::Neutral), CellState::Neutral.color()),
+        player(4),
+        player(2),
+    ];
+    let total_cells = grid.values.len().max(1) as f32;
+
+    ui.vertical_centered(|ui| {
+        ui.add_space(10.0);
+        let (rect, _response) =
+            ui.allocate_exact_size(egui::vec2(width, 30.0), egui::Sense::hover());
+
+        let painter = ui.painter();
+        let mut x = rect.min.x;
+        for (count, color) in segments {
+            let segment_width = (count as f32 / total_cells) * rect.width();
+            let segment = egui::Rect::from_min_size(
+                egui::pos2(x, rect.min.y),
+                egui::vec2(segment_width, rect.height()),
+            );
+            painter.rect_filled(segment, 0.0, color);
+            x += segment_width;
+        }
+    });
+}
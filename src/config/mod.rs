@@ -0,0 +1,547 @@
+use crate::constants::{
+    GRID_SIZE, GRID_THREADS, MAX_CYCLE_PERIOD, MAX_ITERATIONS, MAX_PLAYERS, NB_PLAYERS,
+    PLAYER_ADD_MONEY, PLAYER_MAX_RULES, PLAYER_SPAWN_PROBA, PLAYER_START_MONEY, PLAYER_START_RULES,
+    ROUNDS_TO_WIN, RULE_PICKER_NB_RULES, SHOP_ADD_SPAWN_PROBA, SHOP_NB_RULES,
+    SHOP_PRICE_COUNT_RULE, SHOP_PRICE_DELETE_RULE, SHOP_PRICE_RULE, SHOP_PRICE_SPAWN,
+    SHOP_PRICE_SYMMETRY,
+};
+use crate::grid::neighbourhood::Neighbourhood;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Tuning of a match. \
+/// Defaults to the `standard` preset, built from the values in `constants.rs`.
+/// Missing fields in a config file keep their default value.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Number of players on the board, 2 to `MAX_PLAYERS`
+    pub players: usize,
+    /// With 4 players, play 2v2: players 1 and 3 against players 2 and 4
+    pub teams: bool,
+    pub grid_size: usize,
+    /// Cells around a cell that the rules look at
+    pub neighbourhood: Neighbourhood,
+    /// Players place their starting cells by hand before each battle,
+    /// as many as their spawn probability would scatter
+    pub deployment: bool,
+    /// Threads used to step the grid, `0` for automatic
+    pub threads: usize,
+    pub max_iterations: usize,
+    /// Rounds end early when the grid repeats with a period up to this
+    pub max_cycle_period: usize,
+    pub victory: VictoryCondition,
+    pub conflict: ConflictResolution,
+    pub player_max_rules: usize,
+    pub player_start_rules: usize,
+    pub player_start_money: usize,
+    pub player_add_money: usize,
+    pub player_spawn_proba: f32,
+    pub rule_picker_nb_rules: usize,
+    pub shop_nb_rules: usize,
+    pub shop_price_rule: usize,
+    /// Added to the price of rules matching rotations or mirror images
+    pub shop_price_symmetry: usize,
+    /// Price of rules counting neighbours, which cover too many neighbourhoods to price by coverage
+    pub shop_price_count_rule: usize,
+    pub shop_price_delete_rule: usize,
+    pub shop_price_spawn: usize,
+    pub shop_add_spawn_proba: f32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            players: NB_PLAYERS,
+            teams: false,
+            grid_size: GRID_SIZE,
+            neighbourhood: Neighbourhood::VonNeumann,
+            deployment: false,
+            threads: GRID_THREADS,
+            max_iterations: MAX_ITERATIONS,
+            max_cycle_period: MAX_CYCLE_PERIOD,
+            victory: VictoryCondition::FirstToWins(ROUNDS_TO_WIN),
+            conflict: ConflictResolution::Player1First,
+            player_max_rules: PLAYER_MAX_RULES,
+            player_start_rules: PLAYER_START_RULES,
+            player_start_money: PLAYER_START_MONEY,
+            player_add_money: PLAYER_ADD_MONEY,
+            player_spawn_proba: PLAYER_SPAWN_PROBA,
+            rule_picker_nb_rules: RULE_PICKER_NB_RULES,
+            shop_nb_rules: SHOP_NB_RULES,
+            shop_price_rule: SHOP_PRICE_RULE,
+            shop_price_symmetry: SHOP_PRICE_SYMMETRY,
+            shop_price_count_rule: SHOP_PRICE_COUNT_RULE,
+            shop_price_delete_rule: SHOP_PRICE_DELETE_RULE,
+            shop_price_spawn: SHOP_PRICE_SPAWN,
+            shop_add_spawn_proba: SHOP_ADD_SPAWN_PROBA,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Number of players, clamped to the supported range.
+    pub fn nb_players(&self) -> usize {
+        self.players.clamp(2, MAX_PLAYERS)
+    }
+
+    /// Whether the match is played 2v2.
+    pub fn is_teams(&self) -> bool {
+        self.teams && self.nb_players() == 4
+    }
+
+    /// Number of sides competing for the rounds: teams, or players in free-for-all.
+    pub fn nb_sides(&self) -> usize {
+        if self.is_teams() {
+            2
+        } else {
+            self.nb_players()
+        }
+    }
+
+    /// Side (1 to `nb_sides`) of `player`.
+    pub fn side_of(&self, player: u8) -> u8 {
+        if self.is_teams() {
+            (player - 1) % 2 + 1
+        } else {
+            player
+        }
+    }
+
+    /// Sum per side of values given per player.
+    pub fn side_totals(&self, per_player: &[usize]) -> Vec<usize> {
+        let mut totals = vec![0; self.nb_sides()];
+        for (i, value) in per_player.iter().enumerate() {
+            totals[self.side_of(i as u8 + 1) as usize - 1] += value;
+        }
+        totals
+    }
+
+    pub fn side_name(&self, side: u8) -> String {
+        if self.is_teams() {
+            format!("Team {}", side)
+        } else {
+            format!("Player {}", side)
+        }
+    }
+}
+
+/// Side with the strictly highest total, `None` on a tie.
+pub fn leader(totals: &[usize]) -> Option<u8> {
+    let best = *totals.iter().max()?;
+    let mut leaders = totals.iter().enumerate().filter(|(_, t)| **t == best);
+    match (leaders.next(), leaders.next()) {
+        (Some((i, _)), None) => Some(i as u8 + 1),
+        _ => None,
+    }
+}
+
+/// When a match ends and who wins it.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum VictoryCondition {
+    /// The first player to win `n` rounds wins.
+    FirstToWins(usize),
+    /// After `n` rounds, the player with the most round wins wins.
+    FixedRounds(usize),
+    /// The first player whose cells, summed over the rounds, reach `n` wins.
+    TotalCells(usize),
+}
+
+impl VictoryCondition {
+    /// The `n` of the condition: round wins, rounds or cells.
+    pub fn target(self) -> usize {
+        match self {
+            VictoryCondition::FirstToWins(n)
+            | VictoryCondition::FixedRounds(n)
+            | VictoryCondition::TotalCells(n) => n,
+        }
+    }
+
+    pub fn with_target(self, n: usize) -> VictoryCondition {
+        match self {
+            VictoryCondition::FirstToWins(_) => VictoryCondition::FirstToWins(n),
+            VictoryCondition::FixedRounds(_) => VictoryCondition::FixedRounds(n),
+            VictoryCondition::TotalCells(_) => VictoryCondition::TotalCells(n),
+        }
+    }
+}
+
+impl std::fmt::Display for VictoryCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VictoryCondition::FirstToWins(n) => write!(f, "First to {} round wins", n),
+            VictoryCondition::FixedRounds(n) => write!(f, "Most round wins after {} rounds", n),
+            VictoryCondition::TotalCells(n) => write!(f, "First to {} total cells", n),
+        }
+    }
+}
+
+/// Parse `first_to:<n>`, `rounds:<n>` or `cells:<n>`.
+impl std::str::FromStr for VictoryCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, n) = s
+            .split_once(':')
+            .ok_or(format!("invalid victory condition: {}", s))?;
+        let n = n
+            .parse()
+            .map_err(|_| format!("invalid victory condition: {}", s))?;
+        match kind {
+            "first_to" => Ok(VictoryCondition::FirstToWins(n)),
+            "rounds" => Ok(VictoryCondition::FixedRounds(n)),
+            "cells" => Ok(VictoryCondition::TotalCells(n)),
+            _ => Err(format!("invalid victory condition: {}", s)),
+        }
+    }
+}
+
+/// What happens to a cell matched by rules of several players.
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum ConflictResolution {
+    /// The lowest-numbered player always wins the cell.
+    #[default]
+    Player1First,
+    /// Priority rotates every iteration: Player 1 first, then Player 2, ...
+    Alternating,
+    /// A coin flip per cell among the matching players, derived from the match seed.
+    Random,
+    /// The cell keeps its state.
+    Unchanged,
+    /// The cell becomes neutral.
+    Neutral,
+}
+
+impl ConflictResolution {
+    pub const ALL: [ConflictResolution; 5] = [
+        ConflictResolution::Player1First,
+        ConflictResolution::Alternating,
+        ConflictResolution::Random,
+        ConflictResolution::Unchanged,
+        ConflictResolution::Neutral,
+    ];
+}
+
+impl std::fmt::Display for ConflictResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictResolution::Player1First => write!(f, "Player 1 first"),
+            ConflictResolution::Alternating => write!(f, "Alternating"),
+            ConflictResolution::Random => write!(f, "Random"),
+            ConflictResolution::Unchanged => write!(f, "Unchanged"),
+            ConflictResolution::Neutral => write!(f, "Neutral"),
+        }
+    }
+}
+
+/// Parse `p1_first`, `alternating`, `random`, `unchanged` or `neutral`.
+impl std::str::FromStr for ConflictResolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "p1_first" => Ok(ConflictResolution::Player1First),
+            "alternating" => Ok(ConflictResolution::Alternating),
+            "random" => Ok(ConflictResolution::Random),
+            "unchanged" => Ok(ConflictResolution::Unchanged),
+            "neutral" => Ok(ConflictResolution::Neutral),
+            _ => Err(format!("unknown conflict resolution: {}", s)),
+        }
+    }
+}
+
+/// Named configurations selectable at match start.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Preset {
+    Quick,
+    Standard,
+    Marathon,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Quick, Preset::Standard, Preset::Marathon];
+
+    pub fn config(self) -> GameConfig {
+        let standard = GameConfig::default();
+        match self {
+            Preset::Quick => GameConfig {
+                grid_size: 32,
+                max_iterations: 64,
+                victory: VictoryCondition::FirstToWins(3),
+                player_start_rules: 3,
+                player_start_money: 2,
+                player_spawn_proba: 0.004,
+                shop_add_spawn_proba: 0.0004,
+                ..standard
+            },
+            Preset::Standard => standard,
+            Preset::Marathon => GameConfig {
+                grid_size: 96,
+                max_iterations: 256,
+                victory: VictoryCondition::FirstToWins(10),
+                player_max_rules: 24,
+                rule_picker_nb_rules: 25,
+                shop_nb_rules: 12,
+                ..standard
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Preset::Quick => write!(f, "Quick"),
+            Preset::Standard => write!(f, "Standard"),
+            Preset::Marathon => write!(f, "Marathon"),
+        }
+    }
+}
+
+impl std::str::FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "quick" => Ok(Preset::Quick),
+            "standard" => Ok(Preset::Standard),
+            "marathon" => Ok(Preset::Marathon),
+            _ => Err(format!("unknown preset: {}", s)),
+        }
+    }
+}
+
+/// Error raised while loading or checking a config.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    /// Values that cannot make a playable match
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "config file error: {}", e),
+            ConfigError::Toml(e) => write!(f, "invalid TOML config: {}", e),
+            ConfigError::Json(e) => write!(f, "invalid JSON config: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Toml(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> Self {
+        ConfigError::Json(e)
+    }
+}
+
+impl GameConfig {
+    /// Load a config from a `.toml` file, or a JSON file for any other extension,
+    /// and check it with `validate`.
+    pub fn load(path: impl AsRef<Path>) -> Result<GameConfig, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let config: GameConfig = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&text)?
+        } else {
+            serde_json::from_str(&text)?
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the values that would make the match unplayable, such as an empty grid
+    /// or a rule picker running out of rules before every player has picked.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::Invalid(message));
+        if self.grid_size == 0 {
+            return invalid("grid_size must be at least 1".to_string());
+        }
+        if self.shop_nb_rules == 0 {
+            return invalid("shop_nb_rules must be at least 1".to_string());
+        }
+        if self.player_start_rules > self.player_max_rules {
+            return invalid(format!(
+                "player_start_rules ({}) is more than player_max_rules ({})",
+                self.player_start_rules, self.player_max_rules
+            ));
+        }
+        let picked = self.player_start_rules * self.nb_players();
+        if picked > self.rule_picker_nb_rules {
+            return invalid(format!(
+                "{} players picking {} starting rules need rule_picker_nb_rules of at least {}, not {}",
+                self.nb_players(),
+                self.player_start_rules,
+                picked,
+                self.rule_picker_nb_rules
+            ));
+        }
+        for (name, proba) in [
+            ("player_spawn_proba", self.player_spawn_proba),
+            ("shop_add_spawn_proba", self.shop_add_spawn_proba),
+        ] {
+            if !(0.0..=1.0).contains(&proba) {
+                return invalid(format!("{} must be between 0 and 1, not {}", name, proba));
+            }
+        }
+        for (name, price) in [
+            ("shop_price_rule", self.shop_price_rule),
+            ("shop_price_symmetry", self.shop_price_symmetry),
+            ("shop_price_count_rule", self.shop_price_count_rule),
+            ("shop_price_delete_rule", self.shop_price_delete_rule),
+            ("shop_price_spawn", self.shop_price_spawn),
+        ] {
+            if price == 0 {
+                return invalid(format!("{} must be at least 1", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Override a single field from its name, e.g. `set("grid_size", "128")`. \
+    /// Dashes are accepted in place of underscores, for command-line flags.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value for {}: {}", key, value))
+        }
+
+        match key.replace('-', "_").as_str() {
+            "players" => self.players = parse(key, value)?,
+            "teams" => self.teams = parse(key, value)?,
+            "grid_size" => self.grid_size = parse(key, value)?,
+            "neighbourhood" => self.neighbourhood = parse(key, value)?,
+            "deployment" => self.deployment = parse(key, value)?,
+            "threads" => self.threads = parse(key, value)?,
+            "max_iterations" => self.max_iterations = parse(key, value)?,
+            "max_cycle_period" => self.max_cycle_period = parse(key, value)?,
+            "victory" => self.victory = parse(key, value)?,
+            "conflict" => self.conflict = parse(key, value)?,
+            "player_max_rules" => self.player_max_rules = parse(key, value)?,
+            "player_start_rules" => self.player_start_rules = parse(key, value)?,
+            "player_start_money" => self.player_start_money = parse(key, value)?,
+            "player_add_money" => self.player_add_money = parse(key, value)?,
+            "player_spawn_proba" => self.player_spawn_proba = parse(key, value)?,
+            "rule_picker_nb_rules" => self.rule_picker_nb_rules = parse(key, value)?,
+            "shop_nb_rules" => self.shop_nb_rules = parse(key, value)?,
+            "shop_price_rule" => self.shop_price_rule = parse(key, value)?,
+            "shop_price_symmetry" => self.shop_price_symmetry = parse(key, value)?,
+            "shop_price_count_rule" => self.shop_price_count_rule = parse(key, value)?,
+            "shop_price_delete_rule" => self.shop_price_delete_rule = parse(key, value)?,
+            "shop_price_spawn" => self.shop_price_spawn = parse(key, value)?,
+            "shop_add_spawn_proba" => self.shop_add_spawn_proba = parse(key, value)?,
+            _ => return Err(format!("unknown config field: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Handle the config flags shared by the binaries:
+    /// `--preset <name>`, `--config <file>` and `--set <field>=<value>`. \
+    /// Returns `Ok(false)` if `flag` is not a config flag.
+    /// Check the config with `validate` once every flag is handled.
+    pub fn parse_flag(
+        &mut self,
+        flag: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut value = || args.next().ok_or(format!("missing value for {}", flag));
+        match flag {
+            "--preset" => *self = value()?.parse::<Preset>()?.config(),
+            "--config" => *self = GameConfig::load(value()?)?,
+            "--set" => {
+                let value = value()?;
+                let (key, value) = value
+                    .split_once('=')
+                    .ok_or(format!("expected <field>=<value>, got {}", value))?;
+                self.set(key, value)?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_partial_file() {
+        let config: GameConfig = toml::from_str("grid_size = 16\nshop_price_rule = 3\n").unwrap();
+        assert_eq!(config.grid_size, 16);
+        assert_eq!(config.shop_price_rule, 3);
+        assert_eq!(config.max_iterations, MAX_ITERATIONS);
+    }
+
+    #[test]
+    fn test_config_set() {
+        let mut config = Preset::Quick.config();
+        config.set("max-iterations", "10").unwrap();
+        assert_eq!(config.max_iterations, 10);
+        assert!(config.set("grid_size", "big").is_err());
+        assert!(config.set("colour", "red").is_err());
+        config.set("victory", "cells:4").unwrap();
+        assert_eq!(config.victory, VictoryCondition::TotalCells(4));
+    }
+
+    #[test]
+    fn test_config_validate() {
+        for preset in Preset::ALL {
+            assert!(preset.config().validate().is_ok(), "{}", preset);
+        }
+
+        let invalid = |key: &str, value: &str| {
+            let mut config = GameConfig::default();
+            config.set(key, value).unwrap();
+            matches!(config.validate(), Err(ConfigError::Invalid(_)))
+        };
+        assert!(invalid("grid_size", "0"));
+        assert!(invalid("shop_nb_rules", "0"));
+        assert!(invalid("player_start_rules", "17"));
+        assert!(invalid("player_spawn_proba", "1.5"));
+        assert!(invalid("player_spawn_proba", "-0.1"));
+        assert!(invalid("player_spawn_proba", "NaN"));
+        assert!(invalid("shop_add_spawn_proba", "2"));
+        assert!(!invalid("player_spawn_proba", "1"));
+        for price in [
+            "shop_price_rule",
+            "shop_price_symmetry",
+            "shop_price_count_rule",
+            "shop_price_delete_rule",
+            "shop_price_spawn",
+        ] {
+            assert!(invalid(price, "0"), "{}", price);
+        }
+        // 4 players picking 4 rules each out of 12
+        let mut config = GameConfig::default();
+        config.set("players", "4").unwrap();
+        config.set("rule_picker_nb_rules", "12").unwrap();
+        assert!(config.validate().is_err());
+        config.set("rule_picker_nb_rules", "16").unwrap();
+        assert!(config.validate().is_ok());
+
+        let path = std::env::temp_dir().join("nesw4_test_invalid_config.toml");
+        std::fs::write(&path, "grid_size = 0\n").unwrap();
+        let loaded = GameConfig::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(ConfigError::Invalid(_))));
+    }
+}
@@ -1,26 +1,73 @@
 use eframe::egui::Color32;
-use std::usize;
 
+/// Most players on one board
+pub const MAX_PLAYERS: usize = 4;
+pub const NB_PLAYERS: usize = 2;
 pub const PLAYER_MAX_RULES: usize = 16;
 pub const PLAYER_START_RULES: usize = 4;
 pub const PLAYER_START_MONEY: usize = 0;
 pub const PLAYER_ADD_MONEY: usize = 2;
 pub const PLAYER_SPAWN_PROBA: f32 = 0.001;
+/// Fewest starting cells a player places by hand, whatever their spawn probability
+pub const DEPLOYMENT_MIN_CELLS: usize = 1;
 
 pub const RULE_PICKER_NB_RULES: usize = 20;
+/// Chance for each neighbour of a random rule to be a wildcard instead of an exact state
+pub const RULE_WILDCARD_PROBA: f64 = 0.15;
+/// Chance for a random rule to also match rotations and/or mirror images of its pattern
+pub const RULE_SYMMETRIC_PROBA: f64 = 0.1;
+/// Chance for a random rule to count neighbours instead of matching a pattern
+pub const RULE_COUNT_PROBA: f64 = 0.1;
 
 pub const SHOP_NB_RULES: usize = 8;
 pub const SHOP_PRICE_RULE: usize = 1;
 pub const SHOP_PRICE_DELETE_RULE: usize = 2;
 pub const SHOP_PRICE_SPAWN: usize = 1;
+/// Extra price of symmetric rules
+pub const SHOP_PRICE_SYMMETRY: usize = 2;
+/// Price of count rules, whatever their coverage
+pub const SHOP_PRICE_COUNT_RULE: usize = 4;
 pub const SHOP_ADD_SPAWN_PROBA: f32 = 0.0001;
 
+pub const AI_SIMULATIONS: usize = 2;
+pub const AI_LOOKAHEAD_WIDTH: usize = 3;
+pub const AI_RANDOM_FINISH_PROBA: f64 = 0.3;
+
 pub const GRID_SIZE: usize = 64;
+/// Threads used to step the grid, 0 for one per core on large grids
+pub const GRID_THREADS: usize = 0;
+/// Smallest grid height stepped in parallel when the thread count is automatic
+pub const GRID_PARALLEL_MIN_ROWS: usize = 256;
 pub const CELL_SIZE: f32 = 16.0;
+/// Height of the population, wins and money charts
+pub const CHART_HEIGHT: f32 = 70.0;
 pub const MAX_ITERATIONS: usize = 128;
+/// Longest grid cycle ending a round early, 0 to only stop on a stable grid
+pub const MAX_CYCLE_PERIOD: usize = 8;
+pub const ROUNDS_TO_WIN: usize = 5;
+/// Cells over all rounds a side needs to win a match decided by total cells
+pub const VICTORY_CELLS: usize = 5000;
+/// Time between two iterations at 1x speed
 pub const UPDATE_INTERVAL_MS: u64 = 100;
+/// Speed multipliers of the battle, replays and sandbox
+pub const PLAYBACK_SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+/// Index of the starting speed in `PLAYBACK_SPEEDS`
+pub const PLAYBACK_DEFAULT_SPEED: usize = 2;
+/// Most iterations run in one frame to catch up, longer stalls only play one
+pub const PLAYBACK_MAX_STEPS_PER_FRAME: usize = 32;
+
+pub const SAVE_FILE: &str = "nesw4_save.json";
 
 pub const COLOR_NEUTRAL: Color32 = Color32::from_gray(200);
 pub const COLOR_PLAYER1: Color32 = Color32::from_rgb(100, 100, 255);
 pub const COLOR_PLAYER2: Color32 = Color32::from_rgb(255, 100, 100);
+pub const COLOR_PLAYER3: Color32 = Color32::from_rgb(80, 190, 80);
+pub const COLOR_PLAYER4: Color32 = Color32::from_rgb(230, 170, 40);
+/// Background of the wildcard neighbours of a rule
+pub const COLOR_WILDCARD: Color32 = Color32::from_gray(245);
+pub const COLOR_SYMMETRY_BADGE: Color32 = Color32::from_rgb(250, 210, 80);
 pub const COLOR_GRID_LINE: Color32 = Color32::from_gray(150);
+/// Veil over the cells not captured by the highlighted rule
+pub const COLOR_DIMMED: Color32 = Color32::from_rgba_premultiplied(190, 190, 190, 190);
+/// Frame of the rule whose captures are highlighted
+pub const COLOR_HIGHLIGHT: Color32 = Color32::from_rgb(250, 210, 80);
@@ -0,0 +1,201 @@
+use crate::components;
+use crate::constants::DEPLOYMENT_MIN_CELLS;
+use crate::grid::Grid;
+use crate::grid::cell::CellState;
+use crate::player::Player;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Choice made on the deployment screen.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DeploymentAction {
+    Place(usize),
+    Remove(usize),
+    Done,
+}
+
+/// Starting cells placed by hand before a battle, one player after the other. \
+/// Each player only sees their own cells until everyone is done.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Deployment {
+    /// Cells placed by each player, in player order
+    pub placements: Vec<Vec<usize>>,
+    /// Cells each player may place
+    pub budgets: Vec<usize>,
+    /// Index of the player placing cells, `placements.len()` once everyone is done
+    pub turn: usize,
+}
+
+impl Deployment {
+    /// Budgets of `players` on a grid of `nb_cells` cells:
+    /// the cells their spawn probability would scatter, at least `DEPLOYMENT_MIN_CELLS`
+    /// and at most the whole grid.
+    pub fn new(players: &[Player], nb_cells: usize) -> Deployment {
+        Deployment {
+            placements: vec![Vec::new(); players.len()],
+            budgets: players
+                .iter()
+                .map(|p| {
+                    ((p.spawn_proba * nb_cells as f32).round() as usize)
+                        .max(DEPLOYMENT_MIN_CELLS)
+                        .min(nb_cells)
+                })
+                .collect(),
+            turn: 0,
+        }
+    }
+
+    /// Player placing cells, `None` once everyone is done.
+    pub fn current_player(&self) -> Option<u8> {
+        (self.turn < self.placements.len()).then_some(self.turn as u8 + 1)
+    }
+
+    /// Cells the current player may still place.
+    pub fn remaining(&self) -> usize {
+        match self.current_player() {
+            Some(_) => self.budgets[self.turn] - self.placements[self.turn].len(),
+            None => 0,
+        }
+    }
+
+    /// Place a cell of the current player at `index`. \
+    /// Returns `false` if the cell is already theirs or the budget is spent.
+    pub fn place(&mut self, index: usize) -> bool {
+        if self.remaining() == 0 || self.placements[self.turn].contains(&index) {
+            return false;
+        }
+        self.placements[self.turn].push(index);
+        true
+    }
+
+    /// Take back a cell placed by the current player. Returns `false` if it is not theirs.
+    pub fn remove(&mut self, index: usize) -> bool {
+        let Some(placements) = self.placements.get_mut(self.turn) else {
+            return false;
+        };
+        match placements.iter().position(|i| *i == index) {
+            Some(i) => {
+                placements.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `grid` as seen by `player`: with their own cells only.
+    pub fn view(&self, grid: &Grid, player: u8) -> Grid {
+        let mut view = grid.clone();
+        for &index in &self.placements[player as usize - 1] {
+            view.values.set(index, CellState::player(player));
+        }
+        view
+    }
+
+    /// Put the cells of every player on `grid`.
+    /// Cells placed by several players stay neutral.
+    pub fn apply(&self, grid: &mut Grid) {
+        let mut owners: Vec<Option<CellState>> = vec![None; grid.values.len()];
+        for (i, placements) in self.placements.iter().enumerate() {
+            for &index in placements {
+                owners[index] = match owners[index] {
+                    None => Some(CellState::player(i as u8 + 1)),
+                    Some(_) => Some(CellState::Neutral),
+                };
+            }
+        }
+        for (index, owner) in owners.into_iter().enumerate() {
+            if let Some(owner) = owner {
+                grid.values.set(index, owner);
+            }
+        }
+    }
+
+    /// Show the grid to the current player. \
+    /// Returns the action taken this frame, if any.
+    pub fn show(&self, ui: &mut egui::Ui, grid: &Grid) -> Option<DeploymentAction> {
+        let player = self.current_player()?;
+        let mut action = None;
+        components::text::title(ui);
+
+        ui.vertical_centered(|ui| {
+            ui.label(
+                egui::RichText::new(format!("Player {} deploying", player))
+                    .color(CellState::player(player).color())
+                    .size(18.0)
+                    .strong(),
+            );
+            ui.label(format!(
+                "Cells left: {} / {}",
+                self.remaining(),
+                self.budgets[self.turn]
+            ));
+            ui.label(
+                "Left click places one of your cells, right click takes it back. \
+                 The other players' cells stay hidden until everyone is done, \
+                 cells chosen by several players stay neutral.",
+            );
+            if ui.button("Done").clicked() {
+                action = Some(DeploymentAction::Done);
+            }
+            ui.add_space(5.0);
+
+            let mut view = self.view(grid, player);
+            if let Some(index) = view.show_paintable(ui) {
+                action = Some(if ui.input(|i| i.pointer.secondary_down()) {
+                    DeploymentAction::Remove(index)
+                } else {
+                    DeploymentAction::Place(index)
+                });
+            }
+        });
+
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::game::GameRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_deployment_fog_and_conflicts() {
+        let config = GameConfig::default();
+        let players: Vec<Player> = (0..2)
+            .map(|i| Player::new(format!("Player {}", i + 1), &config))
+            .collect();
+        let mut deployment = Deployment::new(&players, 100);
+        deployment.budgets = vec![2, 2];
+
+        assert!(deployment.place(3));
+        assert!(!deployment.place(3));
+        assert!(deployment.place(4));
+        assert!(!deployment.place(5));
+        assert!(deployment.remove(4));
+        assert!(deployment.place(5));
+
+        deployment.turn = 1;
+        assert!(deployment.place(5));
+        assert!(deployment.place(6));
+        let grid = Grid::new(10, &[], &mut GameRng::seed_from_u64(0));
+        // Player 2 does not see the cells of Player 1
+        assert_eq!(deployment.view(&grid, 2).count(CellState::Player1), 0);
+
+        let mut grid = grid;
+        deployment.apply(&mut grid);
+        assert_eq!(grid.values.get(3), CellState::Player1);
+        assert_eq!(grid.values.get(5), CellState::Neutral);
+        assert_eq!(grid.values.get(6), CellState::Player2);
+    }
+
+    #[test]
+    fn test_budgets_fit_the_grid() {
+        let config = GameConfig::default();
+        let mut player = Player::new("Player 1".to_string(), &config);
+        player.spawn_proba = 1.5;
+        assert_eq!(Deployment::new(&[player.clone()], 100).budgets, [100]);
+        assert_eq!(Deployment::new(&[player], 2).budgets, [2]);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::components;
+use crate::config::leader;
+use crate::game::Game;
+use crate::grid::cell::CellState;
+use crate::rule;
+use eframe::egui;
+
+/// Choice made on the end screen.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum EndAction {
+    Rematch,
+    MainMenu,
+}
+
+/// Summary of a finished match: winner, per-round results, wins and money over the rounds,
+/// money spent and final rule sets.
+pub fn show(ui: &mut egui::Ui, game: &Game) -> Option<EndAction> {
+    let mut action = None;
+    components::text::title(ui);
+
+    let (winner_text, winner_color) = match game.match_winner() {
+        Some(side) => (
+            format!("{} wins the match!", game.config.side_name(side)),
+            CellState::player(side).color(),
+        ),
+        None => ("Draw!".to_string(), egui::Color32::GRAY),
+    };
+    ui.heading(
+        egui::RichText::new(winner_text)
+            .color(winner_color)
+            .size(24.0)
+            .strong(),
+    );
+    ui.label(game.config.victory.to_string());
+    ui.add_space(10.0);
+
+    ui.horizontal(|ui| {
+        if ui
+            .button(egui::RichText::new("Rematch").size(18.0))
+            .clicked()
+        {
+            action = Some(EndAction::Rematch);
+        }
+        if ui
+            .button(egui::RichText::new("Main Menu").size(18.0))
+            .clicked()
+        {
+            action = Some(EndAction::MainMenu);
+        }
+    });
+
+    ui.add_space(10.0);
+    ui.separator();
+    ui.add_space(10.0);
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        components::text::heading(ui, "Rounds");
+        egui::Grid::new("end_rounds")
+            .num_columns(game.players.len() + 3)
+            .striped(true)
+            .spacing([20.0, 5.0])
+            .show(ui, |ui| {
+                ui.strong("Round");
+                for player in &game.players {
+                    ui.strong(format!("{} cells", player.name));
+                }
+                ui.strong("Winner");
+                ui.strong("Iterations");
+                ui.end_row();
+
+                for record in &game.history {
+                    ui.label(record.round.to_string());
+                    for cells in &record.cells {
+                        ui.label(cells.to_string());
+                    }
+                    match leader(&game.config.side_totals(&record.cells)) {
+                        Some(side) => {
+                            ui.colored_label(
+                                CellState::player(side).color(),
+                                game.config.side_name(side),
+                            );
+                        }
+                        None => {
+                            ui.label("Draw");
+                        }
+                    }
+                    ui.label(record.steps.to_string());
+                    ui.end_row();
+                }
+
+                ui.strong("Total");
+                for player in &game.players {
+                    ui.strong(player.total_cells.to_string());
+                }
+                let wins: Vec<String> = game.players.iter().map(|p| p.win.to_string()).collect();
+                ui.strong(wins.join(" - "));
+                ui.end_row();
+            });
+
+        if game.history.iter().any(|r| !r.wins.is_empty()) {
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                let width = (ui.available_width() - 20.0).clamp(100.0, 800.0) / 2.0;
+                game.show_standings(ui, width);
+            });
+        }
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(10.0);
+
+        ui.columns(game.players.len(), |columns| {
+            for (i, (ui, player)) in columns.iter_mut().zip(&game.players).enumerate() {
+                ui.heading(
+                    egui::RichText::new(&player.name)
+                        .color(CellState::player(i as u8 + 1).color())
+                        .size(18.0)
+                        .strong(),
+                );
+                ui.label(
+                    egui::RichText::new(format!("Money spent: {}", player.money_spent)).monospace(),
+                );
+                ui.label(egui::RichText::new(format!("Money left: {}", player.money)).monospace());
+                ui.label(
+                    egui::RichText::new(format!("Spawn: {:.2}%", player.spawn_proba * 100.0))
+                        .monospace(),
+                );
+                ui.add_space(10.0);
+                components::text::heading_small(ui, "Final Rules");
+                rule::show_rules(ui, format!("end_rules {}", player.name), &player.rules);
+            }
+        });
+    });
+
+    action
+}
@@ -0,0 +1,476 @@
+use crate::config::GameConfig;
+use crate::deployment::Deployment;
+use crate::game::{Game, GameRng};
+use crate::grid::cell::CellState;
+use crate::grid::table;
+use crate::player::Player;
+use crate::rule::Rule;
+use crate::rule_picker::RulePicker;
+use crate::shop::Shop;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Stage of the match the engine is currently in.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Phase {
+    RulePicking,
+    /// Players placing their starting cells, see `GameConfig::deployment`
+    Deployment,
+    Battle,
+    Shopping,
+    MatchOver,
+}
+
+/// UI-free match driver. \
+/// Owns the whole round lifecycle (rule picking, battle, shopping) so a match
+/// can be played from tests, scripts or bots without opening a window.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameEngine {
+    pub game: Game,
+    pub rule_picker: RulePicker,
+    #[serde(default)]
+    pub deployment: Deployment,
+    pub phase: Phase,
+    /// Players whose rules were set by `set_rules`, who never pick
+    #[serde(default)]
+    pub fixed_players: Vec<u8>,
+}
+
+impl Default for GameEngine {
+    fn default() -> Self {
+        Self::new(GameConfig::default())
+    }
+}
+
+impl GameEngine {
+    pub fn new(config: GameConfig) -> GameEngine {
+        GameEngine::new_with_seed(config, rand::random())
+    }
+
+    /// Start a match fully reproducible from `seed`.
+    pub fn new_with_seed(config: GameConfig, seed: u64) -> GameEngine {
+        let mut game = Game::new_with_seed(config, seed);
+        game.new_round();
+
+        for (i, player) in game.players.iter_mut().enumerate() {
+            let cell = CellState::player(i as u8 + 1);
+            let rule = Rule::new_direction(cell, game.config.neighbourhood, &mut game.rng);
+            player.rules.push(rule);
+        }
+
+        let rule_picker = RulePicker::new_with_players(&game.players, &game.config, &mut game.rng);
+
+        GameEngine {
+            game,
+            rule_picker,
+            deployment: Deployment::default(),
+            phase: Phase::RulePicking,
+            fixed_players: Vec::new(),
+        }
+    }
+
+    /// Player (1 to 4) whose turn it is to pick a starting rule.
+    pub fn picking_player(&self) -> u8 {
+        self.rule_picker.current_player
+    }
+
+    /// Give the rule at `index` of the rule picker to the player currently choosing. \
+    /// Returns `false` if the pick is not allowed.
+    /// Players pick in turn, and the battle starts once every player owns `player_start_rules` rules.
+    pub fn pick_rule(&mut self, index: usize) -> bool {
+        if self.phase != Phase::RulePicking
+            || !self
+                .rule_picker
+                .rules_available
+                .get(index)
+                .copied()
+                .unwrap_or(false)
+        {
+            return false;
+        }
+
+        let current = self.rule_picker.current_player;
+        let player = &mut self.game.players[current as usize - 1];
+        self.rule_picker.add_rule(player, index);
+        self.rule_picker.current_player = current % self.game.players.len() as u8 + 1;
+        self.update_picking();
+        true
+    }
+
+    /// Replace the rules of `player` with a fixed set, e.g. for tests and tournaments. \
+    /// The player skips their picking turns, however many rules they hold.
+    pub fn set_rules(&mut self, player: u8, rules: Vec<Rule>) {
+        self.game.players[player as usize - 1].rules = rules;
+        if !self.fixed_players.contains(&player) {
+            self.fixed_players.push(player);
+        }
+        if self.phase == Phase::RulePicking {
+            self.update_picking();
+        }
+    }
+
+    /// Start the battle once every player has their starting rules,
+    /// otherwise hand the turn to the next player still needing rules.
+    fn update_picking(&mut self) {
+        let start_rules = self.game.config.player_start_rules;
+        let nb_players = self.game.players.len();
+        let first = self.rule_picker.current_player as usize - 1;
+        let next = (0..nb_players)
+            .map(|offset| (first + offset) % nb_players)
+            .find(|i| {
+                !self.fixed_players.contains(&(*i as u8 + 1))
+                    && self.game.players[*i].rules.len() < start_rules
+            });
+        match next {
+            Some(i) => self.rule_picker.current_player = i as u8 + 1,
+            None => self.start_round(),
+        }
+    }
+
+    /// Deploy the starting cells if the players place them, otherwise start the battle.
+    fn start_round(&mut self) {
+        if !self.game.config.deployment {
+            self.start_battle();
+            return;
+        }
+        self.game.grid.values.fill(CellState::Neutral);
+        self.deployment = Deployment::new(&self.game.players, self.game.grid.values.len());
+        self.phase = Phase::Deployment;
+    }
+
+    /// Player (1 to 4) placing their starting cells, `None` outside the deployment.
+    pub fn deploying_player(&self) -> Option<u8> {
+        match self.phase {
+            Phase::Deployment => self.deployment.current_player(),
+            _ => None,
+        }
+    }
+
+    /// Place a starting cell of the deploying player at `index`. \
+    /// Returns `false` if the placement is not allowed.
+    pub fn deploy(&mut self, index: usize) -> bool {
+        self.phase == Phase::Deployment
+            && index < self.game.grid.values.len()
+            && self.deployment.place(index)
+    }
+
+    /// Take back a starting cell of the deploying player.
+    pub fn undeploy(&mut self, index: usize) -> bool {
+        self.phase == Phase::Deployment && self.deployment.remove(index)
+    }
+
+    /// Place the rest of the deploying player's cells at random, then end their turn,
+    /// stopping early once the whole grid is theirs. \
+    /// The cells are drawn from `deployment_rng`, not from the match RNG.
+    pub fn deploy_at_random(&mut self) {
+        let Some(player) = self.deploying_player() else {
+            return;
+        };
+        let mut rng = self.deployment_rng(player);
+        let nb_cells = self.game.grid.values.len();
+        while self.phase == Phase::Deployment
+            && self.deployment.remaining() > 0
+            && self.deployment.placements[player as usize - 1].len() < nb_cells
+        {
+            let index = rng.gen_range(0..nb_cells);
+            self.deploy(index);
+        }
+        self.end_deployment_turn();
+    }
+
+    /// Random source of `player`'s deployment this round, derived from the seed like
+    /// `table::step_salt`, so the cells a player leaves to chance depend neither on the
+    /// match RNG nor on how the other players deployed.
+    fn deployment_rng(&self, player: u8) -> GameRng {
+        let round = self.game.rounds_played + 1;
+        GameRng::seed_from_u64(table::step_salt(!self.game.seed, round, player as usize))
+    }
+
+    /// End the deploying player's turn. \
+    /// Once every player is done, their cells are put on the grid and the battle starts.
+    pub fn end_deployment_turn(&mut self) {
+        if self.phase != Phase::Deployment {
+            return;
+        }
+        self.deployment.turn += 1;
+        if self.deployment.current_player().is_none() {
+            self.deployment.apply(&mut self.game.grid);
+            self.start_battle();
+        }
+    }
+
+    fn start_battle(&mut self) {
+        self.game.record_round();
+        self.phase = Phase::Battle;
+    }
+
+    /// Advance the battle by one iteration. \
+    /// The round ends when the grid stops changing or after `max_iterations`.
+    /// Returns `true` while the round is still running.
+    pub fn step(&mut self) -> bool {
+        if self.phase != Phase::Battle {
+            return false;
+        }
+        let running = self.game.step();
+        self.check_match_over();
+        running
+    }
+
+    /// Run the current battle until the round is over.
+    pub fn run_round(&mut self) {
+        if self.phase == Phase::Battle {
+            self.game.run_round();
+            self.check_match_over();
+        }
+    }
+
+    fn check_match_over(&mut self) {
+        if self.game.round_over && self.game.match_over() {
+            self.phase = Phase::MatchOver;
+        }
+    }
+
+    /// Leave a finished battle and open the shop, the losers of the round shopping first.
+    pub fn open_shop(&mut self) {
+        if self.phase != Phase::Battle || !self.game.round_over {
+            return;
+        }
+        self.game.shop =
+            Shop::new_with_players(&self.game.players, &self.game.config, &mut self.game.rng);
+        self.game.shop.order = self.game.shop_order.clone();
+        self.phase = Phase::Shopping;
+    }
+
+    /// Shop and every player, for applying shop operations for `shop.current_player()`.
+    pub fn shop_turn(&mut self) -> (&mut Shop, &mut [Player]) {
+        (&mut self.game.shop, &mut self.game.players)
+    }
+
+    /// End the current player's shopping turn. \
+    /// Once every player is done, the next round starts.
+    pub fn end_shop_turn(&mut self) {
+        if self.phase != Phase::Shopping {
+            return;
+        }
+        self.game.shop.turn += 1;
+        if self.game.shop.current_player().is_none() {
+            self.game.new_round();
+            self.start_round();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VictoryCondition;
+    use crate::constants::{MAX_ITERATIONS, PLAYER_SPAWN_PROBA, PLAYER_START_RULES};
+
+    fn pick_all(engine: &mut GameEngine) {
+        let mut index = 0;
+        while engine.phase == Phase::RulePicking {
+            engine.pick_rule(index);
+            index += 1;
+        }
+    }
+
+    #[test]
+    fn test_engine_plays_full_round() {
+        let mut engine = GameEngine::new(GameConfig::default());
+        pick_all(&mut engine);
+        for player in &engine.game.players {
+            assert_eq!(player.rules.len(), PLAYER_START_RULES);
+        }
+
+        engine.run_round();
+        assert!(engine.game.round_over);
+        assert!(engine.game.iteration <= MAX_ITERATIONS);
+        assert!(engine.game.round_result.is_some());
+        // One population entry for the initial grid and one per iteration
+        let record = engine.game.history.last().unwrap();
+        assert_eq!(engine.game.population.len(), record.steps + 1);
+        assert_eq!(engine.game.population.last(), Some(&record.cells));
+        let wins: Vec<usize> = engine.game.players.iter().map(|p| p.win).collect();
+        assert_eq!(record.wins, wins);
+
+        engine.open_shop();
+        assert_eq!(engine.phase, Phase::Shopping);
+        engine.end_shop_turn();
+        engine.end_shop_turn();
+        assert_eq!(engine.phase, Phase::Battle);
+        assert!(!engine.game.round_over);
+    }
+
+    #[test]
+    fn test_engine_same_seed_same_match() {
+        let mut a = GameEngine::new_with_seed(GameConfig::default(), 42);
+        let mut b = GameEngine::new_with_seed(GameConfig::default(), 42);
+        assert_eq!(a.game.grid.values, b.game.grid.values);
+        assert_eq!(a.rule_picker.rules, b.rule_picker.rules);
+        assert_eq!(a.picking_player(), b.picking_player());
+
+        pick_all(&mut a);
+        pick_all(&mut b);
+        a.run_round();
+        b.run_round();
+        assert_eq!(a.game.grid.values, b.game.grid.values);
+        assert_eq!(a.game.iteration, b.game.iteration);
+
+        a.open_shop();
+        b.open_shop();
+        assert_eq!(a.game.shop.rules, b.game.shop.rules);
+    }
+
+    #[test]
+    fn test_engine_fixed_rules_skip_picking() {
+        let mut engine = GameEngine::new_with_seed(GameConfig::default(), 5);
+        let rules = engine.rule_picker.rules[..PLAYER_START_RULES].to_vec();
+        engine.set_rules(1, rules);
+        assert_eq!(engine.picking_player(), 2);
+
+        pick_all(&mut engine);
+        assert_eq!(engine.phase, Phase::Battle);
+        assert_eq!(engine.game.players[1].rules.len(), PLAYER_START_RULES);
+
+        // Fewer fixed rules than `player_start_rules` do not stall the picking
+        let mut engine = GameEngine::new_with_seed(GameConfig::default(), 5);
+        let rules = engine.rule_picker.rules[..2].to_vec();
+        engine.set_rules(1, rules);
+        pick_all(&mut engine);
+        assert_eq!(engine.phase, Phase::Battle);
+        assert_eq!(engine.game.players[0].rules.len(), 2);
+    }
+
+    #[test]
+    fn test_engine_match_ends_on_victory_condition() {
+        let config = GameConfig {
+            victory: VictoryCondition::FixedRounds(2),
+            ..GameConfig::default()
+        };
+        let mut engine = GameEngine::new_with_seed(config, 9);
+        pick_all(&mut engine);
+        engine.run_round();
+        assert_eq!(engine.phase, Phase::Battle);
+
+        engine.open_shop();
+        engine.end_shop_turn();
+        engine.end_shop_turn();
+        engine.run_round();
+        assert_eq!(engine.phase, Phase::MatchOver);
+        assert_eq!(engine.game.rounds_played, 2);
+
+        engine.open_shop();
+        assert_eq!(engine.phase, Phase::MatchOver);
+    }
+
+    #[test]
+    fn test_engine_rejects_chosen_rule() {
+        let mut engine = GameEngine::new(GameConfig::default());
+        assert!(engine.pick_rule(0));
+        assert!(!engine.pick_rule(0));
+    }
+
+    #[test]
+    fn test_engine_four_players_take_turns() {
+        let config = GameConfig {
+            players: 4,
+            teams: true,
+            ..GameConfig::default()
+        };
+        let mut engine = GameEngine::new_with_seed(config, 4);
+        let first = engine.picking_player();
+        engine.pick_rule(0);
+        assert_eq!(engine.picking_player(), first % 4 + 1);
+        pick_all(&mut engine);
+        for player in &engine.game.players {
+            assert_eq!(player.rules.len(), PLAYER_START_RULES);
+        }
+
+        engine.run_round();
+        let teammates_won = [(0, 2), (1, 3)]
+            .iter()
+            .all(|&(a, b)| engine.game.players[a].win == engine.game.players[b].win);
+        assert!(teammates_won);
+
+        engine.open_shop();
+        for _ in 0..4 {
+            assert_eq!(engine.phase, Phase::Shopping);
+            engine.end_shop_turn();
+        }
+        assert_eq!(engine.phase, Phase::Battle);
+    }
+
+    #[test]
+    fn test_engine_deployment_before_battle() {
+        let config = GameConfig {
+            deployment: true,
+            ..GameConfig::default()
+        };
+        let mut engine = GameEngine::new_with_seed(config, 6);
+        pick_all(&mut engine);
+        assert_eq!(engine.phase, Phase::Deployment);
+        assert_eq!(engine.game.grid.count(CellState::Neutral), 64 * 64);
+        assert!(engine.game.history.is_empty());
+
+        assert_eq!(engine.deploying_player(), Some(1));
+        assert!(engine.deploy(10));
+        assert!(!engine.deploy(10));
+        assert!(engine.undeploy(10));
+        assert!(engine.deploy(11));
+        engine.deploy_at_random();
+        assert_eq!(engine.deploying_player(), Some(2));
+        engine.deploy_at_random();
+
+        assert_eq!(engine.phase, Phase::Battle);
+        assert_eq!(engine.game.grid.values.get(11), CellState::Player1);
+        // Budgets from the spawn probability, cells placed twice staying neutral
+        let budget = (PLAYER_SPAWN_PROBA * 64.0 * 64.0).round() as usize;
+        let placed = engine.game.grid.counts(2);
+        assert!(placed.iter().all(|&n| n <= budget && n + 1 >= budget));
+        assert_eq!(engine.game.history.len(), 1);
+
+        engine.run_round();
+        engine.open_shop();
+        engine.end_shop_turn();
+        engine.end_shop_turn();
+        assert_eq!(engine.phase, Phase::Deployment);
+    }
+
+    #[test]
+    fn test_random_deployment_stops_on_full_grid() {
+        let config = GameConfig {
+            deployment: true,
+            grid_size: 3,
+            ..GameConfig::default()
+        };
+        let mut engine = GameEngine::new_with_seed(config, 2);
+        pick_all(&mut engine);
+        // A budget larger than the grid, as in saves made before budgets were clamped
+        engine.deployment.budgets = vec![20, 20];
+        engine.deploy_at_random();
+        assert_eq!(engine.deployment.placements[0].len(), 9);
+        assert_eq!(engine.deploying_player(), Some(2));
+    }
+
+    #[test]
+    fn test_random_deployment_ignores_match_rng() {
+        let config = GameConfig {
+            deployment: true,
+            ..GameConfig::default()
+        };
+        let mut engines = [6, 6].map(|seed| GameEngine::new_with_seed(config.clone(), seed));
+        for engine in &mut engines {
+            pick_all(engine);
+        }
+        // Draws from the match RNG, e.g. by a bot thinking, change nothing
+        let _: u64 = engines[1].game.rng.r#gen();
+        let rng = engines[0].game.rng.clone();
+        for engine in &mut engines {
+            engine.deploy_at_random();
+            engine.deploy_at_random();
+            assert_eq!(engine.phase, Phase::Battle);
+        }
+        assert_eq!(engines[0].game.grid.values, engines[1].game.grid.values);
+        assert_eq!(engines[0].game.rng, rng);
+    }
+}
@@ -1,60 +1,138 @@
-use crate::PLAYER_ADD_MONEY;
-use crate::components;
-use crate::constants::{
-    COLOR_NEUTRAL, COLOR_PLAYER1, COLOR_PLAYER2, GRID_SIZE, MAX_ITERATIONS, PLAYER_SPAWN_PROBA,
-};
+use crate::components::{self, chart};
+use crate::config::{GameConfig, VictoryCondition, leader};
+use crate::constants::{CHART_HEIGHT, COLOR_NEUTRAL};
 use crate::grid::Grid;
-use crate::grid::cell::CellState;
+use crate::grid::cell::{CellState, Cells};
+use crate::grid::table::{self, OutcomeTable};
 use crate::player::Player;
+use crate::replay::RoundRecord;
+use crate::rule::Rule;
 use crate::shop::Shop;
 use eframe::egui;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
+/// Random generator shared by the whole match, fully determined by the match seed.
+pub type GameRng = rand_chacha::ChaCha8Rng;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
-    pub player1: Player,
-    pub player2: Player,
+    /// Player X is `players[X - 1]`
+    pub players: Vec<Player>,
     pub grid: Grid,
-    pub size_grid: usize,
     pub shop: Shop,
     pub iteration: usize,
     pub round_over: bool,
     pub round_result: Option<String>,
-    pub shop_first_player: u8,
-    pub previous_grid_state: Option<Vec<CellState>>,
+    /// Period of the cycle that ended the round early, 1 for a stable grid
+    pub cycle_period: Option<usize>,
+    /// Hash and cells of the last grids of the round, most recent last.
+    /// Not saved, see `rebuild_recent_grids`
+    #[serde(skip)]
+    pub recent_grids: VecDeque<(u64, Cells)>,
+    /// Cells of each player after every iteration of the round, from the initial grid on.
+    /// The other cells are neutral
+    #[serde(default)]
+    pub population: Vec<Vec<usize>>,
+    /// Shopping order of the next shop phase, losers of the last round first
+    pub shop_order: Vec<u8>,
+    pub seed: u64,
+    pub rng: GameRng,
+    pub history: Vec<RoundRecord>,
+    pub config: GameConfig,
+    pub rounds_played: usize,
+    /// Outcome table of the current rule sets, rebuilt when they change
+    #[serde(skip)]
+    table: Option<OutcomeTable>,
+    /// Rule (player, index) whose captures are highlighted on the grid, pinned by a click
+    #[serde(skip)]
+    pub highlight: Option<(u8, usize)>,
+    /// Rule (player, index) under the pointer
+    #[serde(skip)]
+    hovered_rule: Option<(u8, usize)>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(GameConfig::default())
+    }
 }
 
 impl Game {
-    pub fn new() -> Game {
-        let size_grid = GRID_SIZE;
-        let player1 = Player::new(format!("Player 1"));
-        let player2 = Player::new(format!("Player 2"));
-        let shop = Shop::new_with_players(&player1, &player2);
+    pub fn new(config: GameConfig) -> Game {
+        Game::new_with_seed(config, rand::random())
+    }
+
+    /// Create a game whose grids, offered rules and shop stock are all derived from `seed`.
+    pub fn new_with_seed(config: GameConfig, seed: u64) -> Game {
+        let mut rng = GameRng::seed_from_u64(seed);
+        let players: Vec<Player> = (1..=config.nb_players())
+            .map(|i| Player::new(format!("Player {}", i), &config))
+            .collect();
+        let shop = Shop::new_with_players(&players, &config, &mut rng);
+        let spawn_probas: Vec<f32> = players.iter().map(|p| p.spawn_proba).collect();
         Game {
-            player1,
-            player2,
-            grid: Grid::new(size_grid, PLAYER_SPAWN_PROBA, PLAYER_SPAWN_PROBA),
-            size_grid,
+            grid: Grid::new(config.grid_size, &spawn_probas, &mut rng)
+                .with_neighbourhood(config.neighbourhood),
+            shop_order: (1..=players.len() as u8).collect(),
+            players,
             shop,
             iteration: 0,
             round_over: false,
             round_result: None,
-            shop_first_player: 1,
-            previous_grid_state: None,
+            cycle_period: None,
+            recent_grids: VecDeque::new(),
+            population: Vec::new(),
+            seed,
+            rng,
+            history: Vec::new(),
+            config,
+            rounds_played: 0,
+            table: None,
+            highlight: None,
+            hovered_rule: None,
         }
     }
 
     pub fn new_round(&mut self) {
         self.new_grid();
         self.new_shop();
-        self.player1.money += PLAYER_ADD_MONEY;
-        self.player2.money += PLAYER_ADD_MONEY;
+        for player in &mut self.players {
+            player.money += self.config.player_add_money;
+            player.rule_captures.clear();
+        }
+        self.highlight = None;
         self.iteration = 0;
         self.round_over = false;
         self.round_result = None;
-        self.previous_grid_state = None;
+        self.cycle_period = None;
+        self.recent_grids.clear();
+        self.population.clear();
+    }
+
+    /// Record the round about to be played so it can be replayed later.
+    pub fn record_round(&mut self) {
+        self.history.push(RoundRecord {
+            seed: self.seed,
+            round: self.rounds_played + 1,
+            conflict: self.config.conflict,
+            rules: self.players.iter().map(|p| p.rules.clone()).collect(),
+            initial_grid: self.grid.clone(),
+            steps: 0,
+            cells: vec![0; self.players.len()],
+            wins: Vec::new(),
+            money: Vec::new(),
+        });
+    }
+
+    /// Rule sets of every player, in player order.
+    pub fn rule_sets(&self) -> Vec<&[Rule]> {
+        self.players.iter().map(|p| p.rules.as_slice()).collect()
     }
 
     fn new_shop(&mut self) {
-        self.shop = Shop::new_with_players(&self.player1, &self.player2);
+        self.shop = Shop::new_with_players(&self.players, &self.config, &mut self.rng);
     }
 
     pub fn advance_iteration(&mut self) {
@@ -62,34 +140,203 @@ impl Game {
             return;
         }
         self.iteration += 1;
-        if self.iteration >= MAX_ITERATIONS {
+        if self.iteration >= self.config.max_iterations {
             self.end_round();
         }
     }
 
+    /// Advance the battle by one iteration. \
+    /// The round ends when the grid stops changing, repeats itself with a period
+    /// up to `max_cycle_period`, or after `max_iterations`.
+    /// Returns `true` while the round is still running.
+    pub fn step(&mut self) -> bool {
+        if self.round_over {
+            return false;
+        }
+
+        if self.recent_grids.is_empty() {
+            self.rebuild_recent_grids();
+        }
+        if self.population.is_empty() {
+            self.population.push(self.grid.counts(self.players.len()));
+        }
+
+        // Update the grid with the rules of each player
+        let rule_sets: Vec<&[Rule]> = self.players.iter().map(|p| p.rules.as_slice()).collect();
+        let neighbourhood = self.grid.neighbourhood;
+        let conflict = self.config.conflict;
+        let table = match &mut self.table {
+            Some(table) if table.is_for(&rule_sets, neighbourhood, conflict) => table,
+            cached => cached.insert(OutcomeTable::with_conflict(
+                &rule_sets,
+                neighbourhood,
+                conflict,
+            )),
+        };
+        let round = self.rounds_played + 1;
+        table.set_step(
+            self.iteration,
+            table::step_salt(self.seed, round, self.iteration),
+        );
+        let changed = self.grid.next_with_table(table, self.config.threads);
+        for (owner, rule) in self.grid.captures() {
+            self.players[owner as usize - 1].record_capture(rule);
+        }
+        self.population.push(self.grid.counts(self.players.len()));
+        if let Some(record) = self.history.last_mut() {
+            record.steps += 1;
+        }
+
+        // Stop as soon as the grid is stable or cycling
+        self.cycle_period = if changed {
+            self.detect_cycle()
+        } else {
+            Some(1)
+        };
+        if self.cycle_period.is_some() {
+            self.end_round();
+            return false;
+        }
+
+        // Advance the iteration counter
+        self.advance_iteration();
+        !self.round_over
+    }
+
+    /// Fill `recent_grids` with the grids of the round so far, replayed from its record
+    /// after a load, or with the current grid alone when there is none.
+    fn rebuild_recent_grids(&mut self) {
+        let max_period = self.config.max_cycle_period;
+        if max_period == 0 {
+            return;
+        }
+        let round = self.rounds_played + 1;
+        if let Some(record) = self
+            .history
+            .last()
+            .filter(|record| record.round == round && record.steps == self.iteration)
+        {
+            record.for_each_frame(|grid| {
+                cycle_period(&mut self.recent_grids, &grid.values, max_period);
+            });
+        }
+        if self
+            .recent_grids
+            .back()
+            .is_none_or(|(_, cells)| *cells != self.grid.values)
+        {
+            self.recent_grids.clear();
+            self.detect_cycle();
+        }
+    }
+
+    /// Compare the current grid with the last `max_cycle_period` ones
+    /// and return the period if it is one of them.
+    fn detect_cycle(&mut self) -> Option<usize> {
+        cycle_period(
+            &mut self.recent_grids,
+            &self.grid.values,
+            self.config.max_cycle_period,
+        )
+    }
+
+    /// Run the current battle until the round is over.
+    pub fn run_round(&mut self) {
+        while self.step() {}
+    }
+
     pub fn end_round(&mut self) {
         self.round_over = true;
-        let p1_count = self.grid.count(CellState::Player1);
-        let p2_count = self.grid.count(CellState::Player2);
-
-        if p1_count > p2_count {
-            self.player1.win += 1;
-            self.round_result = Some(format!("Player 1 wins!\n{} vs {}", p1_count, p2_count));
-            self.shop_first_player = 2;
-        } else if p2_count > p1_count {
-            self.player2.win += 1;
-            self.round_result = Some(format!("Player 2 wins!\n{} vs {}", p2_count, p1_count));
-            self.shop_first_player = 1;
-        } else {
-            self.round_result = Some(format!("Draw!\n{} - {}", p1_count, p2_count));
+        self.rounds_played += 1;
+        let counts = self.grid.counts(self.players.len());
+        for (player, count) in self.players.iter_mut().zip(&counts) {
+            player.total_cells += count;
+        }
+        if let Some(record) = self.history.last_mut() {
+            record.cells = counts.clone();
+        }
+
+        let totals = self.config.side_totals(&counts);
+        let join = |totals: &[usize], separator| {
+            totals
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        match leader(&totals) {
+            Some(side) => {
+                for (i, player) in self.players.iter_mut().enumerate() {
+                    if self.config.side_of(i as u8 + 1) == side {
+                        player.win += 1;
+                    }
+                }
+                let mut ranked = totals.clone();
+                ranked.sort_unstable_by(|a, b| b.cmp(a));
+                self.round_result = Some(format!(
+                    "{} wins!\n{}",
+                    self.config.side_name(side),
+                    join(&ranked, " vs ")
+                ));
+            }
+            None => self.round_result = Some(format!("Draw!\n{}", join(&totals, " - "))),
+        }
+        if let Some(record) = self.history.last_mut() {
+            record.wins = self.players.iter().map(|p| p.win).collect();
+            record.money = self.players.iter().map(|p| p.money).collect();
+        }
+        // Losers shop first, ties keep the previous order
+        let config = &self.config;
+        self.shop_order
+            .sort_by_key(|player| totals[config.side_of(*player) as usize - 1]);
+
+        if let (Some(result), Some(period)) = (&mut self.round_result, self.cycle_period) {
+            match period {
+                1 => result.push_str("\nStable grid"),
+                _ => result.push_str(&format!("\nCycle of period {}", period)),
+            }
+        }
+    }
+
+    /// Whether the victory condition of the match is reached.
+    pub fn match_over(&self) -> bool {
+        match self.config.victory {
+            VictoryCondition::FirstToWins(n) => self.players.iter().any(|p| p.win >= n),
+            VictoryCondition::FixedRounds(n) => self.rounds_played >= n,
+            VictoryCondition::TotalCells(n) => {
+                let total_cells: Vec<usize> = self.players.iter().map(|p| p.total_cells).collect();
+                self.config
+                    .side_totals(&total_cells)
+                    .iter()
+                    .any(|&total| total >= n)
+            }
         }
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui, shop_clicked: &mut bool) {
+    /// Winning side of the match according to the victory condition, `None` on a draw. \
+    /// Sides are players, or teams in 2v2.
+    pub fn match_winner(&self) -> Option<u8> {
+        let per_player: Vec<usize> = match self.config.victory {
+            VictoryCondition::FirstToWins(_) | VictoryCondition::FixedRounds(_) => {
+                self.players.iter().map(|p| p.win).collect()
+            }
+            VictoryCondition::TotalCells(_) => self.players.iter().map(|p| p.total_cells).collect(),
+        };
+        leader(&self.config.side_totals(&per_player))
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        continue_clicked: &mut bool,
+        replay_clicked: &mut bool,
+    ) {
         components::text::title(ui);
+        ui.label(egui::RichText::new(format!("Seed: {}", self.seed)).monospace());
 
-        let p1_count = self.grid.count(CellState::Player1);
-        let p2_count = self.grid.count(CellState::Player2);
+        let counts = self.grid.counts(self.players.len());
+        // The grid is drawn before the right column, so it highlights the rule hovered last frame
+        let mut hovered = None;
 
         let available_rect = ui.available_rect_before_wrap();
 
@@ -98,11 +345,10 @@ impl Game {
         }
 
         let total_width = available_rect.width();
-        let p1_section_width = total_width / 5.0;
+        let side_width = total_width / 5.0;
         let grid_section_width = total_width * 3.0 / 5.0;
-        let p2_section_width = total_width / 5.0;
 
-        let bar_height = 50.0;
+        let bar_height = 50.0 + CHART_HEIGHT + 25.0;
         let mut game_rect = available_rect;
         game_rect.max.y -= bar_height;
 
@@ -114,18 +360,16 @@ impl Game {
         ui.scope_builder(egui::UiBuilder::new().max_rect(game_rect), |ui| {
             ui.horizontal(|ui| {
                 ui.scope(|ui| {
-                    ui.set_min_width(p1_section_width);
-                    ui.set_max_width(p1_section_width);
-                    ui.vertical(|ui| {
-                        ui.heading(
-                            egui::RichText::new("Player 1")
-                                .color(COLOR_PLAYER1)
-                                .size(18.0)
-                                .strong(),
-                        );
-                        ui.add_space(10.0);
-                        self.player1.show(ui, p1_count);
-                    });
+                    ui.set_min_width(side_width);
+                    ui.set_max_width(side_width);
+                    show_players(
+                        ui,
+                        &self.players,
+                        &counts,
+                        1,
+                        &mut self.highlight,
+                        &mut hovered,
+                    );
                 });
 
                 ui.scope(|ui| {
@@ -133,13 +377,17 @@ impl Game {
                     ui.set_max_width(grid_section_width);
                     ui.vertical_centered(|ui| {
                         let iter_text = if self.round_over {
-                            format!("Round Over")
+                            "Round Over".to_string()
                         } else {
-                            format!("Iteration: {}/{}", self.iteration, MAX_ITERATIONS)
+                            format!(
+                                "Iteration: {}/{}",
+                                self.iteration, self.config.max_iterations
+                            )
                         };
                         components::text::heading(ui, iter_text);
 
-                        self.grid.show(ui);
+                        self.grid
+                            .show_captures(ui, self.hovered_rule.or(self.highlight));
 
                         if let Some(ref result) = self.round_result {
                             ui.add_space(10.0);
@@ -148,70 +396,358 @@ impl Game {
 
                         if self.round_over {
                             ui.add_space(5.0);
-                            if ui.button(egui::RichText::new("Shop").size(18.0)).clicked() {
-                                *shop_clicked = true;
-                            }
+                            ui.horizontal(|ui| {
+                                let continue_text =
+                                    if self.match_over() { "Results" } else { "Shop" };
+                                if ui
+                                    .button(egui::RichText::new(continue_text).size(18.0))
+                                    .clicked()
+                                {
+                                    *continue_clicked = true;
+                                }
+                                if ui
+                                    .button(egui::RichText::new("Replay").size(18.0))
+                                    .clicked()
+                                {
+                                    *replay_clicked = true;
+                                }
+                            });
                         }
                     });
                 });
 
                 ui.scope(|ui| {
-                    ui.set_min_width(p2_section_width);
-                    ui.set_max_width(p2_section_width);
-                    ui.vertical(|ui| {
-                        ui.heading(
-                            egui::RichText::new("Player 2")
-                                .color(COLOR_PLAYER2)
-                                .size(18.0)
-                                .strong(),
-                        );
-                        ui.add_space(10.0);
-                        self.player2.show(ui, p2_count);
-                    });
+                    ui.set_min_width(side_width);
+                    ui.set_max_width(side_width);
+                    show_players(
+                        ui,
+                        &self.players,
+                        &counts,
+                        0,
+                        &mut self.highlight,
+                        &mut hovered,
+                    );
                 });
             });
         });
 
         ui.scope_builder(egui::UiBuilder::new().max_rect(bar_rect), |ui| {
-            let neutral_count = self.grid.count(CellState::Neutral);
-            let total_cells = (p1_count + p2_count + neutral_count) as f32;
-
-            ui.vertical_centered(|ui| {
-                ui.add_space(10.0);
-                let (rect, _response) = ui.allocate_exact_size(
-                    egui::vec2(grid_section_width, 30.0),
-                    egui::Sense::hover(),
-                );
-
-                let painter = ui.painter();
-                let width = rect.width();
-                let p1_width = (p1_count as f32 / total_cells) * width;
-                let neutral_width = (neutral_count as f32 / total_cells) * width;
-                let p2_width = width - p1_width - neutral_width;
-
-                let p1_rect =
-                    egui::Rect::from_min_size(rect.min, egui::vec2(p1_width, rect.height()));
-                let neutral_rect = egui::Rect::from_min_size(
-                    rect.min + egui::vec2(p1_width, 0.0),
-                    egui::vec2(neutral_width, rect.height()),
-                );
-                let p2_rect = egui::Rect::from_min_size(
-                    rect.min + egui::vec2(p1_width + neutral_width, 0.0),
-                    egui::vec2(p2_width, rect.height()),
-                );
-
-                painter.rect_filled(p1_rect, 0.0, COLOR_PLAYER1);
-                painter.rect_filled(neutral_rect, 0.0, COLOR_NEUTRAL);
-                painter.rect_filled(p2_rect, 0.0, COLOR_PLAYER2);
+            components::score_bar::show(ui, grid_section_width, &self.grid);
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                ui.add_space(side_width);
+                if self.round_over && self.history.iter().any(|r| !r.wins.is_empty()) {
+                    let width = (grid_section_width - 20.0) / 3.0;
+                    self.show_population(ui, width);
+                    self.show_standings(ui, width);
+                } else {
+                    self.show_population(ui, grid_section_width);
+                }
             });
         });
+
+        if hovered != self.hovered_rule {
+            self.hovered_rule = hovered;
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// Line chart of the cells of every player and the neutral cells over the round.
+    pub fn show_population(&self, ui: &mut egui::Ui, width: f32) {
+        let total = self.grid.values.len();
+        let mut series: Vec<chart::Series> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(i, player)| chart::Series {
+                name: player.name.clone(),
+                color: CellState::player(i as u8 + 1).color(),
+                values: self.population.iter().map(|counts| counts[i]).collect(),
+            })
+            .collect();
+        series.push(chart::Series {
+            name: "Neutral".to_string(),
+            color: COLOR_NEUTRAL,
+            values: self
+                .population
+                .iter()
+                .map(|counts| total - counts.iter().sum::<usize>())
+                .collect(),
+        });
+        chart::show(
+            ui,
+            egui::vec2(width, CHART_HEIGHT),
+            "Cells",
+            "Iteration",
+            0,
+            &series,
+        );
+    }
+
+    /// Line charts of the wins and money of every player after each round played.
+    pub fn show_standings(&self, ui: &mut egui::Ui, width: f32) {
+        let records: Vec<&RoundRecord> =
+            self.history.iter().filter(|r| !r.wins.is_empty()).collect();
+        let Some(first) = records.first() else {
+            return;
+        };
+        let series = |field: fn(&RoundRecord) -> &Vec<usize>| -> Vec<chart::Series> {
+            (0..self.players.len())
+                .map(|i| chart::Series {
+                    name: self.players[i].name.clone(),
+                    color: CellState::player(i as u8 + 1).color(),
+                    values: records.iter().map(|r| field(r)[i]).collect(),
+                })
+                .collect()
+        };
+        let size = egui::vec2(width, CHART_HEIGHT);
+        let round = first.round;
+        chart::show(ui, size, "Wins", "Round", round, &series(|r| &r.wins));
+        chart::show(ui, size, "Money", "Round", round, &series(|r| &r.money));
     }
 
     fn new_grid(&mut self) {
-        self.grid = Grid::new(
-            self.size_grid,
-            self.player1.spawn_proba,
-            self.player2.spawn_proba,
+        let spawn_probas: Vec<f32> = self.players.iter().map(|p| p.spawn_proba).collect();
+        self.grid = Grid::new(self.config.grid_size, &spawn_probas, &mut self.rng)
+            .with_neighbourhood(self.config.neighbourhood);
+    }
+}
+
+/// Period of the grid of cells `cells` if it is one of the last `max_period` ones in
+/// `recent_grids`, most recent last, which it then joins. \
+/// Grids are told apart by their hash, a matching one being compared cell by cell
+/// so a hash collision is never reported as a cycle.
+pub fn cycle_period(
+    recent_grids: &mut VecDeque<(u64, Cells)>,
+    cells: &Cells,
+    max_period: usize,
+) -> Option<usize> {
+    if max_period == 0 {
+        return None;
+    }
+    let hash = cells.state_hash();
+    let period = recent_grids
+        .iter()
+        .rev()
+        .position(|(recent, grid)| *recent == hash && grid == cells)
+        .map(|i| i + 1);
+    // Reuse the oldest grid's buffer once enough are kept
+    let (_, mut grid) = if recent_grids.len() >= max_period {
+        recent_grids.pop_front().unwrap_or_default()
+    } else {
+        Default::default()
+    };
+    grid.clone_from(cells);
+    recent_grids.push_back((hash, grid));
+    while recent_grids.len() > max_period {
+        recent_grids.pop_front();
+    }
+    period
+}
+
+/// Column of the odd (`parity` 1) or even (`parity` 0) players,
+/// so Player 1 sits on the left of the grid and Player 2 on the right. \
+/// Clicking a rule pins or unpins it in `highlight`, hovering it sets `hovered`.
+fn show_players(
+    ui: &mut egui::Ui,
+    players: &[Player],
+    counts: &[usize],
+    parity: usize,
+    highlight: &mut Option<(u8, usize)>,
+    hovered: &mut Option<(u8, usize)>,
+) {
+    ui.vertical(|ui| {
+        for (i, player) in players.iter().enumerate() {
+            if (i + 1) % 2 != parity {
+                continue;
+            }
+            ui.heading(
+                egui::RichText::new(&player.name)
+                    .color(CellState::player(i as u8 + 1).color())
+                    .size(18.0)
+                    .strong(),
+            );
+            ui.add_space(10.0);
+            let player_id = i as u8 + 1;
+            let selected = highlight
+                .filter(|(player, _)| *player == player_id)
+                .map(|(_, rule)| rule);
+            let (hover, click) = player.show(ui, counts[i], selected);
+            if let Some(rule) = hover {
+                *hovered = Some((player_id, rule));
+            }
+            if let Some(rule) = click {
+                *highlight = match selected == Some(rule) {
+                    true => None,
+                    false => Some((player_id, rule)),
+                };
+            }
+            ui.add_space(20.0);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+
+    #[test]
+    fn test_total_cells_victory() {
+        let config = GameConfig {
+            victory: VictoryCondition::TotalCells(100),
+            ..GameConfig::default()
+        };
+        let mut game = Game::new_with_seed(config, 0);
+        game.players[0].total_cells = 60;
+        game.players[1].total_cells = 99;
+        assert!(!game.match_over());
+        game.players[1].total_cells = 100;
+        assert!(game.match_over());
+        assert_eq!(game.match_winner(), Some(2));
+
+        // In 2v2, the cells of both teammates count
+        let config = GameConfig {
+            players: 4,
+            teams: true,
+            ..game.config.clone()
+        };
+        let mut game = Game::new_with_seed(config, 0);
+        for (player, cells) in game.players.iter_mut().zip([40, 30, 60, 10]) {
+            player.total_cells = cells;
+        }
+        assert!(game.match_over());
+        assert_eq!(game.match_winner(), Some(1));
+    }
+
+    #[test]
+    fn test_cycle_ends_round() {
+        // Two cells swapping owners every iteration
+        let mut game = Game::new_with_seed(GameConfig::default(), 0);
+        game.grid = Grid::from_states(2, 1, &[CellState::Player1, CellState::Player2]);
+        let rule = Rule::exact;
+        use CellState::{Player1 as P1, Player2 as P2};
+        game.players[0].rules = vec![rule(P2, P1, P2, P1)];
+        game.players[1].rules = vec![rule(P1, P2, P1, P2)];
+
+        game.run_round();
+        assert_eq!(game.cycle_period, Some(2));
+        assert_eq!(game.iteration, 1);
+        assert!(game.round_result.unwrap().ends_with("Cycle of period 2"));
+    }
+
+    #[test]
+    fn test_cycle_found_after_load() {
+        // Two cells swapping owners, saved before the cycle shows
+        let mut game = Game::new_with_seed(GameConfig::default(), 0);
+        game.grid = Grid::from_states(2, 1, &[CellState::Player1, CellState::Player2]);
+        let rule = Rule::exact;
+        use CellState::{Player1 as P1, Player2 as P2};
+        game.players[0].rules = vec![rule(P2, P1, P2, P1)];
+        game.players[1].rules = vec![rule(P1, P2, P1, P2)];
+        game.record_round();
+        assert!(game.step());
+
+        // The recent grids are not saved, but replayed from the round record
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(!json.contains("recent_grids"));
+        let mut loaded: Game = serde_json::from_str(&json).unwrap();
+        assert!(!loaded.step());
+        assert_eq!(loaded.cycle_period, Some(2));
+    }
+
+    #[test]
+    fn test_cycle_period() {
+        use CellState::{Neutral as N, Player1 as P1, Player2 as P2};
+        let cells = |states: [CellState; 2]| Cells::from_states(2, &states);
+        let mut recent = VecDeque::new();
+        assert_eq!(cycle_period(&mut recent, &cells([P1, N]), 3), None);
+        assert_eq!(cycle_period(&mut recent, &cells([N, P1]), 3), None);
+        assert_eq!(cycle_period(&mut recent, &cells([P2, N]), 3), None);
+        assert_eq!(cycle_period(&mut recent, &cells([N, P1]), 3), Some(2));
+        assert_eq!(recent.len(), 3);
+        // [P1, N] is no longer among the last 3 grids
+        assert_eq!(cycle_period(&mut recent, &cells([P1, N]), 3), None);
+        assert_eq!(cycle_period(&mut VecDeque::new(), &cells([P1, N]), 0), None);
+
+        // A grid with the same hash but other cells is no cycle
+        let collision = (cells([P2, P2]).state_hash(), cells([N, N]));
+        let mut recent = VecDeque::from([collision]);
+        assert_eq!(cycle_period(&mut recent, &cells([P2, P2]), 3), None);
+    }
+
+    #[test]
+    fn test_rule_stats_follow_rules() {
+        let mut game = Game::new_with_seed(GameConfig::default(), 0);
+        game.grid = Grid::from_states(2, 1, &[CellState::Player1, CellState::Player2]);
+        let rule = Rule::exact;
+        use CellState::{Player1 as P1, Player2 as P2};
+        game.players[0].rules = vec![rule(P2, P1, P2, P1)];
+        game.players[1].rules = vec![rule(P2, P2, P2, P2), rule(P1, P2, P1, P2)];
+
+        game.step();
+        assert_eq!(game.players[1].rule_stats(0), (0, 0));
+        assert_eq!(game.players[1].rule_stats(1), (1, 1));
+
+        // Deleting a rule shifts the statistics of the next ones with it
+        game.players[1].remove_rule(0);
+        assert_eq!(game.players[1].rule_stats(0), (1, 1));
+        game.new_round();
+        assert_eq!(game.players[1].rule_stats(0), (0, 1));
+    }
+
+    #[test]
+    fn test_population_and_history() {
+        let mut game = Game::new_with_seed(GameConfig::default(), 0);
+        game.grid = Grid::from_states(
+            1,
+            3,
+            &[CellState::Player1, CellState::Neutral, CellState::Neutral],
         );
+        use CellState::{Neutral, Player1};
+        // Player 1 spreads down once, then the grid is stable
+        game.players[0].rules = vec![Rule::exact(Player1, Neutral, Neutral, Neutral)];
+        game.players[1].rules.clear();
+        game.players[1].money = 7;
+        game.record_round();
+
+        game.run_round();
+        assert_eq!(game.cycle_period, Some(1));
+        // The initial grid, then one entry per iteration
+        assert_eq!(game.population, [[1, 0], [2, 0], [2, 0]]);
+        let neutral: Vec<usize> = game
+            .population
+            .iter()
+            .map(|counts| 3 - counts.iter().sum::<usize>())
+            .collect();
+        assert_eq!(neutral, [2, 1, 1]);
+
+        let record = game.history.last().unwrap();
+        assert_eq!(record.steps, 2);
+        assert_eq!(record.cells, [2, 0]);
+        assert_eq!(record.wins, [1, 0]);
+        assert_eq!(record.money, [game.config.player_start_money, 7]);
+    }
+
+    #[test]
+    fn test_captures_attributed_past_255_rules() {
+        let mut game = Game::new_with_seed(GameConfig::default(), 0);
+        game.grid = Grid::from_states(
+            1,
+            3,
+            &[CellState::Player1, CellState::Neutral, CellState::Neutral],
+        );
+        use CellState::{Neutral, Player1, Player2};
+        // Only the last rule captures the cell below the Player 1 cell
+        let mut rules = vec![Rule::exact(Player2, Player2, Player2, Player2); 300];
+        rules.push(Rule::exact(Player1, Neutral, Neutral, Neutral));
+        game.players[0].rules = rules;
+        game.players[1].rules.clear();
+
+        game.step();
+        assert_eq!(game.grid.values.get(1), Player1);
+        assert_eq!(game.grid.captured_by[1], Some(300));
+        assert_eq!(game.players[0].rule_stats(300), (1, 1));
+        assert_eq!(game.players[0].rule_stats(300 % 256), (0, 0));
     }
 }
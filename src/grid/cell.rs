@@ -1,27 +1,64 @@
-use crate::{COLOR_GRID_LINE, COLOR_NEUTRAL, COLOR_PLAYER1, COLOR_PLAYER2};
+use crate::{
+    COLOR_GRID_LINE, COLOR_NEUTRAL, COLOR_PLAYER1, COLOR_PLAYER2, COLOR_PLAYER3, COLOR_PLAYER4,
+};
 use eframe::egui;
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+/// State of one cell. `CellState::code` is its index in outcome tables.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+#[repr(u8)]
 pub enum CellState {
-    Neutral,
-    Player1,
-    Player2,
+    Neutral = 0,
+    Player1 = 1,
+    Player2 = 2,
+    Player3 = 3,
+    Player4 = 4,
 }
 
 impl CellState {
-    pub fn show(self, painter: &egui::Painter, rect: egui::Rect) {
+    /// Compact code of the state, also its digit in rule numbers.
+    #[inline]
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Inverse of `code`. Codes above 4 are neutral.
+    #[inline]
+    pub const fn from_code(code: u8) -> CellState {
+        match code {
+            1 => CellState::Player1,
+            2 => CellState::Player2,
+            3 => CellState::Player3,
+            4 => CellState::Player4,
+            _ => CellState::Neutral,
+        }
+    }
+
+    /// Cells of `player` (1 to 4).
+    pub fn player(player: u8) -> CellState {
+        CellState::from_code(player)
+    }
+
+    /// Player owning the cell, `None` if neutral.
+    pub fn owner(self) -> Option<u8> {
         match self {
-            CellState::Neutral => {
-                painter.rect_filled(rect, 0.0, COLOR_NEUTRAL);
-            }
-            CellState::Player1 => {
-                painter.rect_filled(rect, 0.0, COLOR_PLAYER1);
-            }
-            CellState::Player2 => {
-                painter.rect_filled(rect, 0.0, COLOR_PLAYER2);
-            }
+            CellState::Neutral => None,
+            _ => Some(self.code()),
         }
+    }
 
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            CellState::Neutral => COLOR_NEUTRAL,
+            CellState::Player1 => COLOR_PLAYER1,
+            CellState::Player2 => COLOR_PLAYER2,
+            CellState::Player3 => COLOR_PLAYER3,
+            CellState::Player4 => COLOR_PLAYER4,
+        }
+    }
+
+    pub fn show(self, painter: &egui::Painter, rect: egui::Rect) {
+        painter.rect_filled(rect, 0.0, self.color());
         painter.rect_stroke(
             rect,
             0.0,
@@ -29,14 +66,217 @@ impl CellState {
             egui::StrokeKind::Inside,
         );
     }
+
+    /// Paint the cell as a pointy-top hexagon.
+    pub fn show_hex(self, painter: &egui::Painter, center: egui::Pos2, radius: f32) {
+        painter.add(egui::Shape::convex_polygon(
+            hexagon(center, radius),
+            self.color(),
+            egui::Stroke::new(0.5, COLOR_GRID_LINE),
+        ));
+    }
+}
+
+/// Bits of a cell in `Cells`, enough for the codes 0 to 4.
+const CELL_BITS: usize = 3;
+/// Cells packed in one word of `Cells`.
+const CELLS_PER_WORD: usize = u64::BITS as usize / CELL_BITS;
+const CELL_MASK: u64 = (1 << CELL_BITS) - 1;
+/// State of each `CELL_BITS` value, so unpacking needs no branch.
+const STATES: [CellState; 1 << CELL_BITS] = {
+    let mut states = [CellState::Neutral; 1 << CELL_BITS];
+    let mut code = 1;
+    while code <= 4 {
+        states[code] = CellState::from_code(code as u8);
+        code += 1;
+    }
+    states
+};
+/// Cells decoded together when unpacking rows.
+const CELLS_PER_QUAD: usize = 4;
+const QUAD_MASK: u64 = (1 << (CELLS_PER_QUAD * CELL_BITS)) - 1;
+/// States of each group of `CELLS_PER_QUAD` cells.
+static QUADS: [[CellState; CELLS_PER_QUAD]; QUAD_MASK as usize + 1] = {
+    let mut quads = [[CellState::Neutral; CELLS_PER_QUAD]; QUAD_MASK as usize + 1];
+    let mut bits = 0;
+    while bits < quads.len() {
+        let mut i = 0;
+        while i < CELLS_PER_QUAD {
+            quads[bits][i] = STATES[bits >> (i * CELL_BITS) & CELL_MASK as usize];
+            i += 1;
+        }
+        bits += 1;
+    }
+    quads
+};
+
+/// Cell states of a grid, packed `CELL_BITS` bits per cell, by row. \
+/// Every row starts on a new word, so bands of rows can be written independently.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Cells {
+    width: usize,
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl Cells {
+    /// `len` neutral cells, in rows of `width`.
+    pub fn new(width: usize, len: usize) -> Cells {
+        let nb_rows = len.checked_div(width).unwrap_or(0);
+        Cells {
+            width,
+            len,
+            words: vec![0; nb_rows * words_per_row(width)],
+        }
+    }
+
+    /// Pack `states`, in rows of `width`.
+    pub fn from_states(width: usize, states: &[CellState]) -> Cells {
+        let mut cells = Cells::new(width, states.len());
+        if width > 0 {
+            for (words, row) in cells.rows_mut(1).zip(states.chunks(width)) {
+                pack_row(words, row);
+            }
+        }
+        cells
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Word and shift of the cell at `index`.
+    #[inline]
+    fn position(&self, index: usize) -> (usize, usize) {
+        assert!(index < self.len, "cell {index} out of {}", self.len);
+        let (y, x) = (index / self.width, index % self.width);
+        (
+            y * words_per_row(self.width) + x / CELLS_PER_WORD,
+            x % CELLS_PER_WORD * CELL_BITS,
+        )
+    }
+
+    pub fn get(&self, index: usize) -> CellState {
+        let (word, shift) = self.position(index);
+        unpack(self.words[word] >> shift, 0)
+    }
+
+    pub fn set(&mut self, index: usize, state: CellState) {
+        let (word, shift) = self.position(index);
+        let word = &mut self.words[word];
+        *word = *word & !(CELL_MASK << shift) | (state.code() as u64) << shift;
+    }
+
+    /// Set every cell to `state`.
+    pub fn fill(&mut self, state: CellState) {
+        let row = vec![state; self.width];
+        for words in self.rows_mut(1) {
+            pack_row(words, &row);
+        }
+    }
+
+    /// States of the cells, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = CellState> + '_ {
+        let width = self.width;
+        self.words
+            .chunks(words_per_row(width).max(1))
+            .flat_map(move |words| {
+                (0..width).map(move |x| unpack(words[x / CELLS_PER_WORD], x % CELLS_PER_WORD))
+            })
+    }
+
+    pub fn to_vec(&self) -> Vec<CellState> {
+        self.iter().collect()
+    }
+
+    /// FNV-1a hash of the packed cells, equal for equal cells.
+    pub fn state_hash(&self) -> u64 {
+        self.words.iter().fold(0xcbf29ce484222325, |hash, word| {
+            (hash ^ word).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Unpack the row `y` into `out`, which holds `width` cells.
+    pub fn row(&self, y: usize, out: &mut [CellState]) {
+        let words_per_row = words_per_row(self.width);
+        let words = &self.words[y * words_per_row..(y + 1) * words_per_row];
+        // Whole words first, their fixed length letting the loops unroll
+        let mut chunks = out.chunks_exact_mut(CELLS_PER_WORD);
+        for (word, out) in words.iter().zip(&mut chunks) {
+            let mut quads = out.chunks_exact_mut(CELLS_PER_QUAD);
+            for (i, quad) in (&mut quads).enumerate() {
+                let bits = word >> (i * CELLS_PER_QUAD * CELL_BITS);
+                quad.copy_from_slice(&QUADS[(bits & QUAD_MASK) as usize]);
+            }
+            let first = CELLS_PER_WORD / CELLS_PER_QUAD * CELLS_PER_QUAD;
+            for (i, cell) in quads.into_remainder().iter_mut().enumerate() {
+                *cell = unpack(word >> (first * CELL_BITS), i);
+            }
+        }
+        if let Some(word) = words.get(self.width / CELLS_PER_WORD) {
+            for (i, cell) in chunks.into_remainder().iter_mut().enumerate() {
+                *cell = unpack(*word, i);
+            }
+        }
+    }
+
+    /// Words of the cells by bands of `rows` rows, to be written with `pack_row`.
+    pub fn rows_mut(&mut self, rows: usize) -> std::slice::ChunksMut<'_, u64> {
+        let words_per_row = words_per_row(self.width).max(1);
+        self.words.chunks_mut(rows * words_per_row)
+    }
+}
+
+/// Words holding a row of `width` cells.
+#[inline]
+pub fn words_per_row(width: usize) -> usize {
+    width.div_ceil(CELLS_PER_WORD)
+}
+
+/// Pack `row` into the words of a row, see `Cells::rows_mut`.
+pub fn pack_row(words: &mut [u64], row: &[CellState]) {
+    let pack = |cells: &[CellState]| {
+        cells.iter().enumerate().fold(0, |word, (i, cell)| {
+            word | (cell.code() as u64) << (i * CELL_BITS)
+        })
+    };
+    // Whole words first, their fixed length letting the loop unroll
+    let chunks = row.chunks_exact(CELLS_PER_WORD);
+    let rest = chunks.remainder();
+    let mut words = words.iter_mut();
+    for (cells, word) in chunks.zip(&mut words) {
+        *word = pack(cells);
+    }
+    if let Some(word) = words.next() {
+        *word = pack(rest);
+    }
+}
+
+/// State of the `i`-th cell of `word`.
+#[inline]
+fn unpack(word: u64, i: usize) -> CellState {
+    STATES[(word >> (i * CELL_BITS) & CELL_MASK) as usize]
+}
+
+/// Corners of a pointy-top hexagon.
+pub fn hexagon(center: egui::Pos2, radius: f32) -> Vec<egui::Pos2> {
+    (0..6)
+        .map(|i| {
+            let angle = (60.0 * i as f32 - 90.0).to_radians();
+            center + radius * egui::vec2(angle.cos(), angle.sin())
+        })
+        .collect()
 }
 
 impl std::fmt::Display for CellState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             CellState::Neutral => write!(f, "Neutral"),
-            CellState::Player1 => write!(f, "P1"),
-            CellState::Player2 => write!(f, "P2"),
+            _ => write!(f, "P{}", self.code()),
         }
     }
 }
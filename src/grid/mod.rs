@@ -1,113 +1,335 @@
 pub mod cell;
+pub mod neighbourhood;
+pub mod table;
 
-use crate::constants::{COLOR_PLAYER1, COLOR_PLAYER2};
-use crate::grid::cell::CellState;
+use crate::config::{ConflictResolution, leader};
+use crate::constants::{COLOR_DIMMED, GRID_PARALLEL_MIN_ROWS, MAX_PLAYERS};
+use crate::grid::cell::{CellState, Cells, hexagon, pack_row, words_per_row};
+use crate::grid::neighbourhood::Neighbourhood;
+use crate::grid::table::OutcomeTable;
 use crate::rule::Rule;
 use eframe::egui;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 /// Coordinates:
 /// x from left (0) to right (width - 1)
 /// y from top (0) to bottom (height - 1)
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "GridData")]
 pub struct Grid {
     pub width: usize,
     pub height: usize,
-    pub values: Vec<CellState>,
+    /// Packed cells, see `Cells`
+    pub values: Cells,
     pub toric: bool,
+    /// Cells the rules look at
+    pub neighbourhood: Neighbourhood,
+    /// Double buffer for `next`, so stepping never allocates
+    back: Cells,
+    /// Rule of its owner that captured each cell, `None` for cells never captured
+    pub captured_by: Vec<Option<u16>>,
+    /// Outcome table of the last rules given to `next`, rebuilt when they change
+    table: Option<OutcomeTable>,
+}
+
+/// Saved form of a `Grid`.
+#[derive(Serialize, Deserialize)]
+struct GridData {
+    width: usize,
+    height: usize,
+    values: CodesRepr,
+    toric: bool,
+    #[serde(default)]
+    neighbourhood: Neighbourhood,
+}
+
+/// Serialized forms of the cells of a grid: the string of their codes row by row,
+/// e.g. `"0120"`, or the state names of older saves.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CodesRepr {
+    Codes(String),
+    Legacy(Vec<CellState>),
+}
+
+impl TryFrom<GridData> for Grid {
+    type Error = String;
+
+    fn try_from(data: GridData) -> Result<Grid, String> {
+        let values = match data.values {
+            CodesRepr::Codes(codes) => codes
+                .chars()
+                .map(|c| match c.to_digit(10) {
+                    Some(code @ 0..=4) => Ok(CellState::from_code(code as u8)),
+                    _ => Err(format!("invalid cell code: {}", c)),
+                })
+                .collect::<Result<_, _>>()?,
+            CodesRepr::Legacy(values) => values,
+        };
+        if values.len() != data.width * data.height {
+            return Err(format!(
+                "{} cells for a {}x{} grid",
+                values.len(),
+                data.width,
+                data.height
+            ));
+        }
+        Ok(Grid::from_states(data.width, data.height, &values)
+            .with_neighbourhood(data.neighbourhood)
+            .with_toric(data.toric))
+    }
+}
+
+impl Serialize for Grid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GridData {
+            width: self.width,
+            height: self.height,
+            values: CodesRepr::Codes(
+                self.values
+                    .iter()
+                    .map(|cell| char::from(b'0' + cell.code()))
+                    .collect(),
+            ),
+            toric: self.toric,
+            neighbourhood: self.neighbourhood,
+        }
+        .serialize(serializer)
+    }
 }
 
 impl Grid {
     /// Create a new grid. \
-    /// `pX_spawn_p` is the spawn probability of the player X. \
-    /// `p1_spawn_p + p2_spawn_p` should be smaller than 1.0.
-    pub fn new(size: usize, p1_spawn_p: f32, p2_spawn_p: f32) -> Grid {
-        Grid {
-            width: size,
-            height: size,
-            values: {
-                use rand::Rng;
-                let mut values: Vec<CellState> = vec![];
-                for _ in 0..size * size {
-                    let mut rng = rand::thread_rng();
-                    let x: f32 = rng.r#gen();
-                    if x <= p1_spawn_p {
-                        values.push(CellState::Player1);
-                    } else if x <= p1_spawn_p + p2_spawn_p {
-                        values.push(CellState::Player2);
-                    } else {
-                        values.push(CellState::Neutral);
+    /// `spawn_probas[X - 1]` is the spawn probability of the player X. \
+    /// The sum of the probabilities should be smaller than 1.0.
+    pub fn new(size: usize, spawn_probas: &[f32], rng: &mut impl Rng) -> Grid {
+        Grid::from_states(size, size, &{
+            let mut values: Vec<CellState> = vec![];
+            for _ in 0..size * size {
+                let x: f32 = rng.r#gen();
+                let mut threshold = 0.0;
+                let mut cell = CellState::Neutral;
+                for (i, p) in spawn_probas.iter().enumerate() {
+                    threshold += p;
+                    if x <= threshold {
+                        cell = CellState::player(i as u8 + 1);
+                        break;
                     }
                 }
-                values
-            },
+                values.push(cell);
+            }
+            values
+        })
+    }
+
+    /// Toric grid of `width` x `height` cells, `values` row by row.
+    pub fn from_states(width: usize, height: usize, values: &[CellState]) -> Grid {
+        assert_eq!(values.len(), width * height, "{width}x{height} grid");
+        Grid {
+            width,
+            height,
+            values: Cells::from_states(width, values),
             toric: true,
+            neighbourhood: Neighbourhood::VonNeumann,
+            back: Cells::default(),
+            captured_by: Vec::new(),
+            table: None,
         }
     }
 
+    /// The same grid, stepped over `neighbourhood`.
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Grid {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    /// The same grid, wrapping around its edges if `toric`.
+    pub fn with_toric(mut self, toric: bool) -> Grid {
+        self.toric = toric;
+        self
+    }
+
     pub fn count(&self, value: CellState) -> usize {
-        self.values.iter().filter(|v| **v == value).count()
-    }
-
-    /// Change grid state by applying all the rules of the players.
-    /// `rules_p1` and `rules_p2` should not have any rule in common.
-    pub fn next(&mut self, rules_p1: &Vec<Rule>, rules_p2: &Vec<Rule>) {
-        let mut new_values = self.values.clone();
-
-        for y in 0..self.height {
-            'cell: for x in 0..self.width {
-                let current_idx = self.get_idx(x, y);
-
-                // Get the cell states for checking rules.
-                // top, right, bottom, left refer to the cells relative to the current cell
-                let (top_idx, right_idx, bottom_idx, left_idx) = if self.toric {
-                    // Toroidal - wrap around
-                    let top_y = if y == 0 { self.height - 1 } else { y - 1 };
-                    let bottom_y = if y == self.height - 1 { 0 } else { y + 1 };
-                    let left_x = if x == 0 { self.width - 1 } else { x - 1 };
-                    let right_x = if x == self.width - 1 { 0 } else { x + 1 };
-                    (
-                        self.get_idx(x, top_y),
-                        self.get_idx(right_x, y),
-                        self.get_idx(x, bottom_y),
-                        self.get_idx(left_x, y),
-                    )
-                } else {
-                    // Non-toroidal - out of bounds
-                    if y == 0 || y == self.height - 1 || x == 0 || x == self.width - 1 {
-                        continue;
-                    }
-                    (
-                        self.get_idx(x, y - 1),
-                        self.get_idx(x + 1, y),
-                        self.get_idx(x, y + 1),
-                        self.get_idx(x - 1, y),
-                    )
-                };
-
-                let top_state = self.values[top_idx];
-                let right_state = self.values[right_idx];
-                let bottom_state = self.values[bottom_idx];
-                let left_state = self.values[left_idx];
-
-                // Check if any rule matches
-                for rule in rules_p1 {
-                    if rule.next(top_state, right_state, bottom_state, left_state) {
-                        new_values[current_idx] = CellState::Player1;
-                        continue 'cell;
-                    }
-                }
-                for rule in rules_p2 {
-                    if rule.next(top_state, right_state, bottom_state, left_state) {
-                        new_values[current_idx] = CellState::Player2;
-                        continue 'cell;
-                    }
-                }
+        self.values.iter().filter(|v| *v == value).count()
+    }
+
+    /// Cells owned by each of the first `nb_players` players.
+    pub fn counts(&self, nb_players: usize) -> Vec<usize> {
+        let mut counts = [0; MAX_PLAYERS + 1];
+        for cell in self.values.iter() {
+            counts[cell.code() as usize] += 1;
+        }
+        counts[1..=nb_players].to_vec()
+    }
+
+    /// Change grid state by applying all the rules of the players, `rules[X - 1]` being
+    /// the rules of player X.
+    /// Cells matched by rules of several players go to the lowest-numbered one,
+    /// see `OutcomeTable::with_conflict` for the other resolutions. \
+    /// Returns `true` if at least one cell changed.
+    pub fn next(&mut self, rules: &[&[Rule]]) -> bool {
+        self.next_parallel(rules, 1)
+    }
+
+    /// Same as `next`, with the rows split across `threads` threads. \
+    /// `0` uses one thread per core once the grid has `GRID_PARALLEL_MIN_ROWS` rows.
+    /// The result is identical to the serial update.
+    pub fn next_parallel(&mut self, rules: &[&[Rule]], threads: usize) -> bool {
+        let table = match self.table.take() {
+            Some(table)
+                if table.is_for(rules, self.neighbourhood, ConflictResolution::Player1First) =>
+            {
+                table
             }
+            _ => OutcomeTable::new(rules, self.neighbourhood),
+        };
+        let changed = self.next_with_table(&table, threads);
+        self.table = Some(table);
+        changed
+    }
+
+    /// Change grid state with a precomputed outcome table, see `next_parallel` for `threads`.
+    /// The table's neighbourhood is used, it should be the grid's one. \
+    /// The new state is written to the back buffer, which is then swapped with `values`.
+    /// Returns `true` if at least one cell changed.
+    pub fn next_with_table(&mut self, table: &OutcomeTable, threads: usize) -> bool {
+        let threads = match threads {
+            0 if self.height >= GRID_PARALLEL_MIN_ROWS => {
+                std::thread::available_parallelism().map_or(1, |n| n.get())
+            }
+            0 => 1,
+            n => n.min(self.height),
+        };
+        if self.values.is_empty() {
+            return false;
+        }
+        let mut back = std::mem::take(&mut self.back);
+        if back.len() != self.values.len() {
+            back = Cells::new(self.width, self.values.len());
+        }
+        let mut captured_by = std::mem::take(&mut self.captured_by);
+        captured_by.resize(self.values.len(), None);
+
+        let changed = if threads <= 1 {
+            let out = back.rows_mut(self.height).next().unwrap_or_default();
+            self.next_rows(table, 0, out, &mut captured_by)
+        } else {
+            // Each thread writes its own band of rows, reading the whole current grid
+            // so the rows at the seams see their real neighbours
+            let band = self.height.div_ceil(threads);
+            let this = &*self;
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = back
+                    .rows_mut(band)
+                    .zip(captured_by.chunks_mut(band * this.width))
+                    .enumerate()
+                    .map(|(i, (out, captured))| {
+                        scope.spawn(move || this.next_rows(table, i * band, out, captured))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .fold(false, |changed, handle| handle.join().unwrap() | changed)
+            })
+        };
+
+        self.back = std::mem::replace(&mut self.values, back);
+        self.captured_by = captured_by;
+        changed
+    }
+
+    /// (owner, rule) of each cell captured by the last step.
+    pub fn captures(&self) -> impl Iterator<Item = (u8, usize)> + '_ {
+        self.values
+            .iter()
+            .zip(self.back.iter())
+            .zip(&self.captured_by)
+            .filter(|((cell, previous), _)| cell != previous)
+            .filter_map(|((cell, _), rule)| Some((cell.owner()?, (*rule)? as usize)))
+    }
+
+    /// Update the rows starting at `first_row` into `out`, the packed words of whole rows,
+    /// recording the rule capturing each changed cell into `captured`, the same rows
+    /// of `captured_by`. \
+    /// Rows are unpacked as they are reached, keeping the rows above and below at hand.
+    /// Returns `true` if at least one cell changed.
+    fn next_rows(
+        &self,
+        table: &OutcomeTable,
+        first_row: usize,
+        out: &mut [u64],
+        captured: &mut [Option<u16>],
+    ) -> bool {
+        let (width, height) = (self.width, self.height);
+        let mut rows: [Vec<CellState>; 3] = Default::default();
+        for (dy, row) in rows.iter_mut().enumerate() {
+            row.resize(width, CellState::Neutral);
+            self.values.row((first_row + height + dy - 1) % height, row);
         }
+        let mut new_row = vec![CellState::Neutral; width];
+        let mut changed = false;
+        for (i, (out, captured)) in out
+            .chunks_mut(words_per_row(width))
+            .zip(captured.chunks_mut(width))
+            .enumerate()
+        {
+            let y = first_row + i;
+            if i > 0 {
+                rows.rotate_left(1);
+                self.values.row((y + 1) % height, &mut rows[2]);
+            }
 
-        self.values = new_values;
+            if !self.toric && (y == 0 || y == height - 1) {
+                // Non-toroidal - border cells never change
+                pack_row(out, &rows[1]);
+                continue;
+            }
+            let offsets = table.neighbourhood().offsets(y);
+            changed |= next_row(
+                table,
+                y * width,
+                [&rows[0], &rows[1], &rows[2]],
+                offsets,
+                &mut new_row,
+                captured,
+                self.toric,
+            );
+            pack_row(out, &new_row);
+        }
+        changed
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui) {
+        self.show_with_sense(ui, egui::Sense::hover(), None);
+    }
+
+    /// Show the grid, dimming every cell but those captured by
+    /// the rule `highlight.1` of the player `highlight.0`, if any.
+    pub fn show_captures(&mut self, ui: &mut egui::Ui, highlight: Option<(u8, usize)>) {
+        self.show_with_sense(ui, egui::Sense::hover(), highlight);
+    }
+
+    /// Show the grid, returning the index of the cell under the pointer
+    /// while it is pressed or dragged, to paint it.
+    pub fn show_paintable(&mut self, ui: &mut egui::Ui) -> Option<usize> {
+        self.show_with_sense(ui, egui::Sense::click_and_drag(), None)
+    }
+
+    /// Whether the cell at `index` is dimmed when highlighting the captures of `highlight`.
+    fn dimmed(&self, index: usize, highlight: Option<(u8, usize)>) -> bool {
+        highlight.is_some_and(|(player, rule)| {
+            self.values.get(index).owner() != Some(player)
+                || self.captured_by.get(index) != Some(&Some(rule as u16))
+        })
+    }
+
+    fn show_with_sense(
+        &self,
+        ui: &mut egui::Ui,
+        sense: egui::Sense,
+        highlight: Option<(u8, usize)>,
+    ) -> Option<usize> {
         let available_rect = ui.available_rect_before_wrap();
         let padding = 10.0;
         let stroke_width = 10.0;
@@ -116,7 +338,11 @@ impl Grid {
         let max_height = available_rect.height() - padding * 2.0 - stroke_width * 2.0;
 
         if max_width.is_nan() || max_height.is_nan() || max_width <= 0.0 || max_height <= 0.0 {
-            return;
+            return None;
+        }
+
+        if self.neighbourhood == Neighbourhood::Hex {
+            return self.show_hex(ui, max_width, max_height, stroke_width, sense, highlight);
         }
 
         let cell_size_w = max_width / self.width as f32;
@@ -132,7 +358,7 @@ impl Grid {
                 grid_width_px + stroke_width * 2.0,
                 grid_height_px + stroke_width * 2.0,
             ),
-            egui::Sense::hover(),
+            sense,
         );
 
         // The grid area is the allocated area shrunk by the stroke width
@@ -146,21 +372,103 @@ impl Grid {
                 let cell_rect =
                     egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(cell_size, cell_size));
 
-                let cell_idx = row * self.width + col;
-                let cell_state = self.values[cell_idx];
+                let index = self.get_idx(col, row);
+                self.values.get(index).show(&painter, cell_rect);
+                if self.dimmed(index, highlight) {
+                    painter.rect_filled(cell_rect, 0.0, COLOR_DIMMED);
+                }
+            }
+        }
+
+        self.show_border(&painter, grid_rect, stroke_width);
 
-                cell_state.show(&painter, cell_rect);
+        let pos = pressed_pos(&response)?;
+        if !grid_rect.contains(pos) {
+            return None;
+        }
+        let col = ((pos.x - grid_rect.min.x) / cell_size) as usize;
+        let row = ((pos.y - grid_rect.min.y) / cell_size) as usize;
+        Some(self.get_idx(col.min(self.width - 1), row.min(self.height - 1)))
+    }
+
+    /// Hexagonal cells, odd rows shifted half a cell to the right.
+    fn show_hex(
+        &self,
+        ui: &mut egui::Ui,
+        max_width: f32,
+        max_height: f32,
+        stroke_width: f32,
+        sense: egui::Sense,
+        highlight: Option<(u8, usize)>,
+    ) -> Option<usize> {
+        // A hexagon of radius r is sqrt(3) * r wide, rows are 1.5 * r apart
+        let sqrt3 = 3f32.sqrt();
+        let radius_w = max_width / ((self.width as f32 + 0.5) * sqrt3);
+        let radius_h = max_height / (1.5 * self.height as f32 + 0.5);
+        let radius = radius_w.min(radius_h).clamp(4.5, 29.0);
+
+        let grid_width_px = (self.width as f32 + 0.5) * sqrt3 * radius;
+        let grid_height_px = (1.5 * self.height as f32 + 0.5) * radius;
+
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(
+                grid_width_px + stroke_width * 2.0,
+                grid_height_px + stroke_width * 2.0,
+            ),
+            sense,
+        );
+        let grid_rect = response.rect.shrink(stroke_width);
+        let origin = grid_rect.min;
+        let center = |col: usize, row: usize| {
+            let shift = if row % 2 == 1 { 0.5 } else { 0.0 };
+            origin
+                + egui::vec2(
+                    (col as f32 + 0.5 + shift) * sqrt3 * radius,
+                    (1.5 * row as f32 + 1.0) * radius,
+                )
+        };
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let index = self.get_idx(col, row);
+                self.values
+                    .get(index)
+                    .show_hex(&painter, center(col, row), radius);
+                if self.dimmed(index, highlight) {
+                    painter.add(egui::Shape::convex_polygon(
+                        hexagon(center(col, row), radius),
+                        COLOR_DIMMED,
+                        egui::Stroke::NONE,
+                    ));
+                }
             }
         }
+        self.show_border(&painter, grid_rect, stroke_width);
 
-        let p1_count = self.count(CellState::Player1);
-        let p2_count = self.count(CellState::Player2);
-        let border_color = if p1_count > p2_count {
-            COLOR_PLAYER1
-        } else if p2_count > p1_count {
-            COLOR_PLAYER2
-        } else {
-            egui::Color32::BLACK
+        // Nearest cell center among the rows around the pointer
+        let pos = pressed_pos(&response)?;
+        if !grid_rect.contains(pos) {
+            return None;
+        }
+        let row = ((pos.y - origin.y) / (1.5 * radius)) as usize;
+        (row.saturating_sub(1)..(row + 2).min(self.height))
+            .map(|row| {
+                let shift = if row % 2 == 1 { 0.5 } else { 0.0 };
+                let col = ((pos.x - origin.x) / (sqrt3 * radius) - shift).max(0.0) as usize;
+                (col.min(self.width - 1), row)
+            })
+            .min_by(|a, b| {
+                let distance = |(col, row)| center(col, row).distance(pos);
+                distance(*a).total_cmp(&distance(*b))
+            })
+            .map(|(col, row)| self.get_idx(col, row))
+    }
+
+    /// Frame in the color of the player leading on the grid.
+    fn show_border(&self, painter: &egui::Painter, grid_rect: egui::Rect, stroke_width: f32) {
+        let border_color = match leader(&self.counts(MAX_PLAYERS)) {
+            Some(player) => CellState::player(player).color(),
+            None => egui::Color32::BLACK,
         };
 
         painter.rect_stroke(
@@ -175,3 +483,260 @@ impl Grid {
         y * self.width + x
     }
 }
+
+/// Pointer position while it is pressed on `response`.
+fn pressed_pos(response: &egui::Response) -> Option<egui::Pos2> {
+    if response.is_pointer_button_down_on() {
+        response.interact_pointer_pos()
+    } else {
+        None
+    }
+}
+
+/// Update one row into `out`, given the rows above and below it
+/// and the (dx, dy) `offsets` of the neighbours, recording into `captured`
+/// the rule capturing each changed cell. \
+/// `first_cell` is the index of the first cell of the row in the grid.
+/// Returns `true` if at least one cell changed.
+fn next_row(
+    table: &OutcomeTable,
+    first_cell: usize,
+    rows: [&[CellState]; 3],
+    offsets: &[(isize, isize)],
+    out: &mut [CellState],
+    captured: &mut [Option<u16>],
+    toric: bool,
+) -> bool {
+    let row = rows[1];
+    let width = row.len();
+    let nb_states = table.nb_states();
+    if !toric {
+        // Non-toroidal - border cells never change
+        out[0] = row[0];
+        out[width - 1] = row[width - 1];
+    }
+    let mut changed = false;
+    if toric {
+        // Toroidal - wrap around
+        for x in [0, width - 1] {
+            let current = row[x];
+            let pattern = offsets.iter().fold(0, |index, &(dx, dy)| {
+                let x = (x as isize + dx).rem_euclid(width as isize) as usize;
+                index * nb_states + rows[(dy + 1) as usize][x].code() as usize
+            });
+            let new = table.next(pattern, first_cell + x, current);
+            out[x] = new;
+            if new != current {
+                captured[x] = capture(table, pattern, new);
+                changed = true;
+            }
+        }
+    }
+    let args = (table, first_cell, rows);
+    changed
+        | match *offsets {
+            [a, b, c, d] => next_inner(args, [a, b, c, d], out, captured),
+            [a, b, c, d, e, f] => next_inner(args, [a, b, c, d, e, f], out, captured),
+            [a, b, c, d, e, f, g, h] => next_inner(args, [a, b, c, d, e, f, g, h], out, captured),
+            _ => unreachable!("neighbourhoods have 4, 6 or 8 cells"),
+        }
+}
+
+/// Update the cells of a row but its first and last ones, which need no wrapping,
+/// for a neighbourhood of `N` cells, see `next_row`.
+#[inline(always)]
+fn next_inner<const N: usize>(
+    (table, first_cell, rows): (&OutcomeTable, usize, [&[CellState]; 3]),
+    offsets: [(isize, isize); N],
+    out: &mut [CellState],
+    captured: &mut [Option<u16>],
+) -> bool {
+    let width = rows[1].len();
+    if width < 3 {
+        return false;
+    }
+    let inner = width - 2;
+    let nb_states = table.nb_states();
+    // Neighbour `k` of the `i`-th inner cell is `columns[k][i]`, slices of the same length
+    // so the loop needs no bound checks
+    let columns: [&[CellState]; N] = std::array::from_fn(|k| {
+        let (dx, dy) = offsets[k];
+        let start = (dx + 1) as usize;
+        &rows[(dy + 1) as usize][start..start + inner]
+    });
+    let current = &rows[1][1..width - 1];
+    let out = &mut out[1..width - 1];
+    let captured = &mut captured[1..width - 1];
+    let mut changed = false;
+    for i in 0..inner {
+        let mut pattern = 0;
+        for column in &columns {
+            pattern = pattern * nb_states + column[i].code() as usize;
+        }
+        let new = table.next(pattern, first_cell + 1 + i, current[i]);
+        out[i] = new;
+        if new != current[i] {
+            captured[i] = capture(table, pattern, new);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Rule of its owner capturing a cell that became `new` with neighbourhood `pattern`.
+#[inline]
+fn capture(table: &OutcomeTable, pattern: usize, new: CellState) -> Option<u16> {
+    new.owner()
+        .and_then(|player| table.rule(pattern, player))
+        .map(|rule| rule as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Straightforward cell-by-cell update, as a reference for the table-driven one. \
+    /// Also returns the first rule of the new owner matching each cell.
+    fn naive_next(grid: &Grid, rules: &[&[Rule]]) -> (Vec<CellState>, Vec<Option<usize>>) {
+        let (w, h) = (grid.width, grid.height);
+        let mut values = grid.values.to_vec();
+        let mut fired = vec![None; values.len()];
+        for y in 0..h {
+            for x in 0..w {
+                if !grid.toric && (x == 0 || y == 0 || x == w - 1 || y == h - 1) {
+                    continue;
+                }
+                let cells: Vec<CellState> = grid
+                    .neighbourhood
+                    .offsets(y)
+                    .iter()
+                    .map(|&(dx, dy)| {
+                        let nx = (x as isize + dx).rem_euclid(w as isize) as usize;
+                        let ny = (y as isize + dy).rem_euclid(h as isize) as usize;
+                        grid.values.get(ny * w + nx)
+                    })
+                    .collect();
+                if let Some(player) = (0..rules.len()).find(|&i| {
+                    let owner = i as u8 + 1;
+                    rules[i].iter().any(|r| r.next(owner, &cells))
+                }) {
+                    let owner = player as u8 + 1;
+                    values[y * w + x] = CellState::player(owner);
+                    fired[y * w + x] = rules[player].iter().position(|r| r.next(owner, &cells));
+                }
+            }
+        }
+        (values, fired)
+    }
+
+    fn random_rules(
+        nb_players: usize,
+        neighbourhood: Neighbourhood,
+        rng: &mut impl Rng,
+    ) -> Vec<Vec<Rule>> {
+        (0..nb_players)
+            .map(|_| {
+                (0..6)
+                    .map(|_| Rule::new(nb_players, neighbourhood, rng))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn table_matches_naive_update() {
+        let mut rng = crate::game::GameRng::seed_from_u64(7);
+        use Neighbourhood::{Hex, Moore, VonNeumann};
+        for (toric, nb_players, neighbourhood) in [
+            (true, 2, VonNeumann),
+            (false, 2, VonNeumann),
+            (true, 4, VonNeumann),
+            (true, 2, Moore),
+            (false, 3, Moore),
+            (true, 2, Hex),
+            (false, 4, Hex),
+        ] {
+            let mut grid = Grid::new(38, &vec![0.15; nb_players], &mut rng);
+            grid.toric = toric;
+            grid.neighbourhood = neighbourhood;
+            let rules = random_rules(nb_players, neighbourhood, &mut rng);
+            let rules: Vec<&[Rule]> = rules.iter().map(Vec::as_slice).collect();
+            let table = OutcomeTable::new(&rules, neighbourhood);
+            for _ in 0..20 {
+                let (expected, fired) = naive_next(&grid, &rules);
+                let changed = grid.next_with_table(&table, 1);
+                assert_eq!(changed, grid.back != grid.values);
+                assert_eq!(grid.values.to_vec(), expected);
+                for (i, rule) in fired.iter().enumerate() {
+                    if grid.values.get(i) != grid.back.get(i) {
+                        assert_eq!(grid.captured_by[i], rule.map(|rule| rule as u16));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_cells_round_trip() {
+        let mut rng = crate::game::GameRng::seed_from_u64(3);
+        // Rows of 23 cells spill one cell past their first word
+        let states: Vec<CellState> = (0..23 * 5)
+            .map(|_| CellState::from_code(rng.gen_range(0..=4)))
+            .collect();
+        let mut grid = Grid::from_states(23, 5, &states);
+        assert_eq!(grid.values.to_vec(), states);
+        assert!((0..states.len()).all(|i| grid.values.get(i) == states[i]));
+
+        grid.values.set(22, CellState::Player4);
+        grid.values.set(23, CellState::Neutral);
+        assert_eq!(grid.values.get(21), states[21]);
+        assert_eq!(grid.values.get(22), CellState::Player4);
+        assert_eq!(grid.values.get(23), CellState::Neutral);
+        assert_eq!(grid.values.get(24), states[24]);
+
+        let json = serde_json::to_string(&grid).unwrap();
+        let loaded: Grid = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.values, grid.values);
+    }
+
+    #[test]
+    fn grid_serde() {
+        use CellState::{Neutral as N, Player1 as P1, Player2 as P2};
+        let grid = Grid::from_states(2, 2, &[N, P1, P2, N]);
+        let json = serde_json::to_string(&grid).unwrap();
+        assert!(json.contains(r#""values":"0120""#));
+        let loaded: Grid = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.values, grid.values);
+
+        // Grids of older saves
+        let old = r#"{"width":2,"height":2,"values":["Neutral","Player1","Player2","Neutral"],"toric":true}"#;
+        assert_eq!(
+            serde_json::from_str::<Grid>(old).unwrap().values,
+            grid.values
+        );
+        let wrong = r#"{"width":2,"height":2,"values":"0125","toric":true}"#;
+        assert!(serde_json::from_str::<Grid>(wrong).is_err());
+        let short = r#"{"width":2,"height":2,"values":"012","toric":true}"#;
+        assert!(serde_json::from_str::<Grid>(short).is_err());
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let mut rng = crate::game::GameRng::seed_from_u64(11);
+        for toric in [true, false] {
+            let mut serial = Grid::new(29, &[0.2, 0.2], &mut rng);
+            serial.toric = toric;
+            let rules = random_rules(2, Neighbourhood::VonNeumann, &mut rng);
+            let rules: Vec<&[Rule]> = rules.iter().map(Vec::as_slice).collect();
+            let mut parallel: Vec<Grid> = (2..=5).map(|_| serial.clone()).collect();
+            for _ in 0..20 {
+                let changed = serial.next(&rules);
+                for (threads, grid) in (2..=5).zip(parallel.iter_mut()) {
+                    assert_eq!(grid.next_parallel(&rules, threads), changed);
+                    assert_eq!(grid.values, serial.values);
+                }
+            }
+        }
+    }
+}
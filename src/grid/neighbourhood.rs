@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+/// Top, right, bottom, left.
+const VON_NEUMANN: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+/// Clockwise from the top.
+const MOORE: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+/// Clockwise from the top-right, on even rows.
+const HEX_EVEN_ROW: [(isize, isize); 6] = [(0, -1), (1, 0), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+/// Clockwise from the top-right, on odd rows, which are shifted half a cell to the right.
+const HEX_ODD_ROW: [(isize, isize); 6] = [(1, -1), (1, 0), (1, 1), (0, 1), (-1, 0), (0, -1)];
+
+/// Cells around a cell that its rules look at. Chosen per match.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Neighbourhood {
+    /// The 4 orthogonal neighbours
+    #[default]
+    VonNeumann,
+    /// The 8 surrounding cells, corners included
+    Moore,
+    /// The 6 neighbours of a hexagonal cell, odd rows being shifted half a cell to the right
+    Hex,
+}
+
+impl Neighbourhood {
+    pub const ALL: [Neighbourhood; 3] = [
+        Neighbourhood::VonNeumann,
+        Neighbourhood::Moore,
+        Neighbourhood::Hex,
+    ];
+
+    /// Number of neighbours.
+    pub fn arity(self) -> usize {
+        match self {
+            Neighbourhood::VonNeumann => 4,
+            Neighbourhood::Moore => 8,
+            Neighbourhood::Hex => 6,
+        }
+    }
+
+    /// Neighbourhood with `arity` neighbours.
+    pub fn from_arity(arity: usize) -> Option<Neighbourhood> {
+        Neighbourhood::ALL.into_iter().find(|n| n.arity() == arity)
+    }
+
+    /// (dx, dy) of the neighbours of a cell on row `y`, in rule order.
+    #[inline]
+    pub fn offsets(self, y: usize) -> &'static [(isize, isize)] {
+        match self {
+            Neighbourhood::VonNeumann => &VON_NEUMANN,
+            Neighbourhood::Moore => &MOORE,
+            Neighbourhood::Hex if y.is_multiple_of(2) => &HEX_EVEN_ROW,
+            Neighbourhood::Hex => &HEX_ODD_ROW,
+        }
+    }
+
+    /// Neighbours a quarter turn (a sixth for hexagons) moves along.
+    pub fn rotation_step(self) -> usize {
+        match self {
+            Neighbourhood::VonNeumann | Neighbourhood::Hex => 1,
+            Neighbourhood::Moore => 2,
+        }
+    }
+
+    /// Neighbour facing neighbour `i` in a left/right mirror.
+    pub fn mirror(self, i: usize) -> usize {
+        match self {
+            Neighbourhood::VonNeumann | Neighbourhood::Moore => (self.arity() - i) % self.arity(),
+            Neighbourhood::Hex => 5 - i,
+        }
+    }
+}
+
+impl std::fmt::Display for Neighbourhood {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Neighbourhood::VonNeumann => write!(f, "Von Neumann (4)"),
+            Neighbourhood::Moore => write!(f, "Moore (8)"),
+            Neighbourhood::Hex => write!(f, "Hexagonal (6)"),
+        }
+    }
+}
+
+/// Parse `von_neumann`, `moore` or `hex`.
+impl std::str::FromStr for Neighbourhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "von_neumann" | "vonneumann" => Ok(Neighbourhood::VonNeumann),
+            "moore" => Ok(Neighbourhood::Moore),
+            "hex" | "hexagonal" => Ok(Neighbourhood::Hex),
+            _ => Err(format!("unknown neighbourhood: {}", s)),
+        }
+    }
+}
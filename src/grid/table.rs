@@ -0,0 +1,327 @@
+use crate::config::ConflictResolution;
+use crate::grid::cell::CellState;
+use crate::grid::neighbourhood::Neighbourhood;
+use crate::rule::Rule;
+use std::sync::Arc;
+
+/// Neighbourhood of a pattern index, written into `cells`. \
+/// Base-`nb_states` digits, the first neighbour being the most significant,
+/// like the number shown by `Rule`.
+pub fn pattern(index: usize, nb_states: usize, cells: &mut [CellState]) {
+    let mut index = index;
+    for cell in cells.iter_mut().rev() {
+        *cell = CellState::from_code((index % nb_states) as u8);
+        index /= nb_states;
+    }
+}
+
+/// Call `f` with the index of every pattern whose neighbour `i` is one of the states
+/// of `accepted[i]`, bit `s` standing for the state of code `s`.
+fn for_each_pattern(accepted: &[u8], nb_states: usize, f: &mut impl FnMut(usize)) {
+    fn visit(accepted: &[u8], nb_states: usize, index: usize, f: &mut impl FnMut(usize)) {
+        let Some((first, rest)) = accepted.split_first() else {
+            f(index);
+            return;
+        };
+        for state in 0..nb_states {
+            if first & 1 << state != 0 {
+                visit(rest, nb_states, index * nb_states + state, f);
+            }
+        }
+    }
+    visit(accepted, nb_states, 0, f);
+}
+
+/// Cells of each player in each of the first `nb_patterns` patterns over `nb_states` states. \
+/// A pattern has the cells of the pattern without its last neighbour, plus that one.
+fn player_counts(nb_patterns: usize, nb_states: usize) -> Vec<[u8; 4]> {
+    let mut counts = vec![[0u8; 4]; nb_patterns];
+    for index in 1..nb_patterns {
+        let mut pattern_counts = counts[index / nb_states];
+        if let Some(player) = (index % nb_states).checked_sub(1) {
+            pattern_counts[player] += 1;
+        }
+        counts[index] = pattern_counts;
+    }
+    counts
+}
+
+/// Rule index of `OutcomeTable::rule` for players matching with none of their rules.
+const NO_RULE: u16 = u16::MAX;
+/// Outcome of the patterns leaving the cell as it is.
+const KEEP: u8 = u8::MAX;
+/// Outcome of the patterns whose conflict is resolved at each step.
+const CONTESTED: u8 = u8::MAX - 1;
+
+/// New state of a cell for each neighbourhood. \
+/// The players matching each neighbourhood are computed once from the rules,
+/// so the grid update is a lookup per cell; the conflicts between players
+/// are then resolved for the current step, see `set_step`.
+#[derive(Clone, Debug)]
+pub struct OutcomeTable {
+    /// Players matching each pattern, bit `p - 1` for player `p`
+    matching: Arc<[u8]>,
+    /// Code of the new state for each pattern, `KEEP` or `CONTESTED`
+    outcomes: Arc<[u8]>,
+    /// First rule of each player matching each pattern, `NO_RULE` if none,
+    /// at `pattern * nb_players + player - 1`
+    first_rule: Arc<[u16]>,
+    /// Rules the table was built from
+    rules: Arc<[Vec<Rule>]>,
+    neighbourhood: Neighbourhood,
+    nb_states: usize,
+    conflict: ConflictResolution,
+    /// Player index with the priority this step, for `Alternating`
+    priority: usize,
+    salt: u64,
+}
+
+impl OutcomeTable {
+    /// `rules` holds the rule set of each player, in player order.
+    /// Lower-numbered players are checked first.
+    pub fn new(rules: &[&[Rule]], neighbourhood: Neighbourhood) -> OutcomeTable {
+        OutcomeTable::with_conflict(rules, neighbourhood, ConflictResolution::Player1First)
+    }
+
+    /// Table resolving the patterns matched by several players with `conflict`. \
+    /// Patterns are over the states of the players in `rules` and neutral,
+    /// so cells of other players must not be on the grid.
+    pub fn with_conflict(
+        rules: &[&[Rule]],
+        neighbourhood: Neighbourhood,
+        conflict: ConflictResolution,
+    ) -> OutcomeTable {
+        let nb_players = rules.len();
+        let nb_states = nb_players + 1;
+        let arity = neighbourhood.arity();
+        let nb_patterns = nb_states.pow(arity as u32);
+        let mut matching = vec![0u8; nb_patterns];
+        let mut first_rule = vec![NO_RULE; nb_patterns * nb_players];
+        // Cells of each player in each pattern, for count rules
+        let mut counts: Vec<[u8; 4]> = Vec::new();
+        for (i, rules) in rules.iter().enumerate() {
+            let owner = i as u8 + 1;
+            for (r, rule) in rules.iter().enumerate() {
+                // Earlier rules are marked first, so `first_rule` keeps the first match
+                let mut mark = |index: usize| {
+                    matching[index] |= 1 << i;
+                    let first = &mut first_rule[index * nb_players + i];
+                    if *first == NO_RULE {
+                        *first = r as u16;
+                    }
+                };
+                match rule {
+                    // Only the patterns a rule matches are visited,
+                    // rather than every neighbourhood for every rule
+                    Rule::Pattern(pattern) if pattern.atoms.len() == arity => {
+                        for accepted in pattern.accepted(owner, nb_states) {
+                            for_each_pattern(&accepted, nb_states, &mut mark);
+                        }
+                    }
+                    Rule::Pattern(_) => {}
+                    Rule::Count(count) => {
+                        if counts.is_empty() {
+                            counts = player_counts(nb_patterns, nb_states);
+                        }
+                        for (index, counts) in counts.iter().enumerate() {
+                            let mine = counts[i] as usize;
+                            let total = counts.iter().map(|&c| c as usize).sum::<usize>();
+                            if count.matches_counts(mine, total - mine) {
+                                mark(index);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let outcomes = matching
+            .iter()
+            .map(|&mask| {
+                let first = mask.trailing_zeros() as u8 + 1;
+                match (mask.count_ones(), conflict) {
+                    (0, _) | (2.., ConflictResolution::Unchanged) => KEEP,
+                    (1, _) | (2.., ConflictResolution::Player1First) => first,
+                    (2.., ConflictResolution::Neutral) => CellState::Neutral.code(),
+                    (2.., ConflictResolution::Alternating | ConflictResolution::Random) => {
+                        CONTESTED
+                    }
+                }
+            })
+            .collect();
+        OutcomeTable {
+            matching: matching.into(),
+            outcomes,
+            first_rule: first_rule.into(),
+            rules: rules.iter().map(|rules| rules.to_vec()).collect(),
+            neighbourhood,
+            nb_states,
+            conflict,
+            priority: 0,
+            salt: 0,
+        }
+    }
+
+    /// Whether the table was built from these rules and settings, so it can be reused.
+    pub fn is_for(
+        &self,
+        rules: &[&[Rule]],
+        neighbourhood: Neighbourhood,
+        conflict: ConflictResolution,
+    ) -> bool {
+        self.neighbourhood == neighbourhood
+            && self.conflict == conflict
+            && self.rules.len() == rules.len()
+            && self.rules.iter().zip(rules).all(|(a, b)| a == b)
+    }
+
+    /// Prepare the table for the step at `iteration`. \
+    /// `salt` drives the random tie-breaks, see `step_salt`.
+    pub fn set_step(&mut self, iteration: usize, salt: u64) {
+        self.priority = iteration % self.rules.len().max(1);
+        self.salt = salt;
+    }
+
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    /// Number of cell states in the patterns: neutral and each player.
+    pub fn nb_states(&self) -> usize {
+        self.nb_states
+    }
+
+    /// First rule of `player` (1 to 4) matching the neighbourhood `pattern`.
+    pub fn rule(&self, pattern: usize, player: u8) -> Option<usize> {
+        let rule = self.first_rule[pattern * self.rules.len() + player as usize - 1];
+        (rule != NO_RULE).then_some(rule as usize)
+    }
+
+    /// Index of the neighbourhood `cells`, see `pattern`.
+    pub fn pattern_index(&self, cells: &[CellState]) -> usize {
+        cells.iter().fold(0, |index, cell| {
+            index * self.nb_states + cell.code() as usize
+        })
+    }
+
+    /// New state of the cell at index `cell`, currently `current`, with neighbourhood `pattern`.
+    #[inline]
+    pub fn next(&self, pattern: usize, cell: usize, current: CellState) -> CellState {
+        let outcome = self.outcomes[pattern];
+        if outcome == CONTESTED {
+            return self.resolve(self.matching[pattern], cell);
+        }
+        // Without branches, outcomes are hard to predict on mixed grids
+        let keep = ((outcome == KEEP) as u8).wrapping_neg();
+        CellState::from_code((current.code() & keep) | (outcome & !keep))
+    }
+
+    /// Winner of the cell at index `cell` among the players of `matching`,
+    /// for the conflicts changing with the step.
+    fn resolve(&self, matching: u8, cell: usize) -> CellState {
+        let first = |mask: u8| CellState::player(mask.trailing_zeros() as u8 + 1);
+        match self.conflict {
+            ConflictResolution::Alternating => {
+                // First matching player from the one with the priority, wrapping around
+                let from_priority = matching >> self.priority << self.priority;
+                first(if from_priority != 0 {
+                    from_priority
+                } else {
+                    matching
+                })
+            }
+            ConflictResolution::Random => {
+                // Pick the n-th matching player
+                let n = mix(self.salt ^ cell as u64) % matching.count_ones() as u64;
+                let mut mask = matching;
+                for _ in 0..n {
+                    mask &= mask - 1;
+                }
+                first(mask)
+            }
+            // Resolved when the table is built
+            ConflictResolution::Player1First
+            | ConflictResolution::Unchanged
+            | ConflictResolution::Neutral => first(matching),
+        }
+    }
+}
+
+/// Salt of the random tie-breaks of one step, so replays flip the same coins.
+pub fn step_salt(seed: u64, round: usize, iteration: usize) -> u64 {
+    mix(seed ^ mix(((round as u64) << 32) ^ iteration as u64))
+}
+
+/// SplitMix64 finalizer.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflict_resolution() {
+        use CellState::{Neutral, Player1, Player2};
+        // Both players own the rule "all neighbours neutral except the top one is Player 1"
+        let rule = Rule::exact(Player1, Neutral, Neutral, Neutral);
+        let rules = [rule];
+        let outcome = |conflict, iteration| {
+            let mut table =
+                OutcomeTable::with_conflict(&[&rules, &rules], Neighbourhood::VonNeumann, conflict);
+            table.set_step(iteration, 0);
+            let index = table.pattern_index(&[Player1, Neutral, Neutral, Neutral]);
+            table.next(index, 0, Player2)
+        };
+
+        assert_eq!(outcome(ConflictResolution::Player1First, 1), Player1);
+        assert_eq!(outcome(ConflictResolution::Alternating, 0), Player1);
+        assert_eq!(outcome(ConflictResolution::Alternating, 1), Player2);
+        assert_eq!(outcome(ConflictResolution::Unchanged, 0), Player2);
+        assert_eq!(outcome(ConflictResolution::Neutral, 0), Neutral);
+
+        let mut table = OutcomeTable::with_conflict(
+            &[&rules, &rules],
+            Neighbourhood::VonNeumann,
+            ConflictResolution::Random,
+        );
+        table.set_step(0, 42);
+        let index = table.pattern_index(&[Player1, Neutral, Neutral, Neutral]);
+        let flips: Vec<CellState> = (0..64)
+            .map(|cell| table.next(index, cell, Neutral))
+            .collect();
+        assert!(flips.contains(&Player1) && flips.contains(&Player2));
+    }
+
+    #[test]
+    fn test_table_matches_rules() {
+        use rand::SeedableRng;
+        // Random rules of 3 players, plus wildcards, symmetries and a count rule
+        let mut rng = crate::game::GameRng::seed_from_u64(7);
+        let rules: Vec<Vec<Rule>> = (0..3)
+            .map(|_| {
+                let mut rules: Vec<Rule> = (0..6)
+                    .map(|_| Rule::new(3, Neighbourhood::Hex, &mut rng))
+                    .collect();
+                rules.push("mo*!3+m/r".parse().unwrap());
+                rules.push("m>=2 o<=1".parse().unwrap());
+                rules
+            })
+            .collect();
+        let rules: Vec<&[Rule]> = rules.iter().map(Vec::as_slice).collect();
+        let table = OutcomeTable::new(&rules, Neighbourhood::Hex);
+
+        let mut cells = [CellState::Neutral; 6];
+        for index in 0..table.nb_states().pow(6) {
+            pattern(index, table.nb_states(), &mut cells);
+            for (i, player_rules) in rules.iter().enumerate() {
+                let player = i as u8 + 1;
+                let first = player_rules.iter().position(|r| r.next(player, &cells));
+                assert_eq!(table.rule(index, player), first, "pattern {:?}", cells);
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::ai::{Bot, Difficulty};
+use crate::components;
+use crate::config::{ConfigError, ConflictResolution, GameConfig, Preset, VictoryCondition};
+use crate::constants::{MAX_PLAYERS, ROUNDS_TO_WIN, VICTORY_CELLS};
+use crate::engine::GameEngine;
+use crate::grid::neighbourhood::Neighbourhood;
+use crate::rule::Rule;
+use eframe::egui;
+
+/// Choice made on the landing screen.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum LandingAction {
+    NewMatch,
+    ContinueMatch,
+    /// Try rules on a hand-painted grid, with the settings chosen
+    Sandbox,
+}
+
+/// Match setup shown before the first rule pick.
+pub struct LandingScreen {
+    pub preset: Preset,
+    pub config: GameConfig,
+    /// Difficulty of the computer playing every player but Player 1, `None` for humans only
+    pub versus_ai: Option<Difficulty>,
+    pub seed: String,
+    pub show_help: bool,
+    /// Rule typed in the help, to look it up
+    pub rule_text: String,
+    pub error: Option<String>,
+}
+
+impl Default for LandingScreen {
+    fn default() -> Self {
+        LandingScreen {
+            preset: Preset::Standard,
+            config: Preset::Standard.config(),
+            versus_ai: None,
+            seed: String::new(),
+            show_help: false,
+            rule_text: String::new(),
+            error: None,
+        }
+    }
+}
+
+impl LandingScreen {
+    /// Seed typed by the user, `None` for a random one.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed.trim().parse().ok()
+    }
+
+    /// Start from the settings of `preset`.
+    pub fn set_preset(&mut self, preset: Preset) {
+        self.preset = preset;
+        self.config = preset.config();
+    }
+
+    /// Set the N of the victory condition, keeping its kind.
+    pub fn set_target(&mut self, n: usize) {
+        self.config.victory = self.config.victory.with_target(n);
+    }
+
+    /// Engine of a match with the chosen settings and seed, if they make a playable match.
+    pub fn new_match(&self) -> Result<GameEngine, ConfigError> {
+        self.config.validate()?;
+        let config = self.config.clone();
+        Ok(match self.seed() {
+            Some(seed) => GameEngine::new_with_seed(config, seed),
+            None => GameEngine::new(config),
+        })
+    }
+
+    /// Computer players of every player but Player 1 when playing against the AI,
+    /// seeded from the match `seed`.
+    pub fn bots(&self, seed: u64) -> Vec<Bot> {
+        match self.versus_ai {
+            Some(difficulty) => (2..=self.config.nb_players() as u8)
+                .map(|player| Bot::new(player, difficulty, seed ^ player as u64))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, can_continue: bool) -> Option<LandingAction> {
+        let mut action = None;
+        components::text::title(ui);
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(egui::RichText::new("New Match").size(18.0))
+                    .clicked()
+                {
+                    action = Some(LandingAction::NewMatch);
+                }
+                if ui
+                    .add_enabled(
+                        can_continue,
+                        egui::Button::new(egui::RichText::new("Continue Saved Match").size(18.0)),
+                    )
+                    .clicked()
+                {
+                    action = Some(LandingAction::ContinueMatch);
+                }
+                if ui
+                    .button(egui::RichText::new("Sandbox").size(18.0))
+                    .clicked()
+                {
+                    action = Some(LandingAction::Sandbox);
+                }
+            });
+            if let Some(ref error) = self.error {
+                ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+            }
+
+            ui.add_space(10.0);
+            ui.separator();
+            ui.add_space(10.0);
+
+            components::text::heading(ui, "Opponent");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.versus_ai, None, "Human");
+                for difficulty in [
+                    Difficulty::Random,
+                    Difficulty::Greedy,
+                    Difficulty::LookAhead,
+                ] {
+                    ui.radio_value(
+                        &mut self.versus_ai,
+                        Some(difficulty),
+                        format!("AI: {}", difficulty),
+                    );
+                }
+            });
+
+            ui.add_space(10.0);
+            ui.separator();
+            ui.add_space(10.0);
+
+            components::text::heading(ui, "Settings");
+            ui.horizontal(|ui| {
+                ui.label("Preset:");
+                for preset in Preset::ALL {
+                    if ui
+                        .selectable_label(self.preset == preset, preset.to_string())
+                        .clicked()
+                    {
+                        self.set_preset(preset);
+                    }
+                }
+            });
+            ui.add_space(5.0);
+            egui::Grid::new("landing_settings")
+                .num_columns(2)
+                .spacing([10.0, 5.0])
+                .show(ui, |ui| {
+                    ui.label("Players");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Slider::new(&mut self.config.players, 2..=MAX_PLAYERS));
+                        ui.add_enabled(
+                            self.config.players == 4,
+                            egui::Checkbox::new(&mut self.config.teams, "2v2 teams (1 & 3 vs 2 & 4)"),
+                        );
+                    });
+                    ui.end_row();
+
+                    ui.label("Grid size");
+                    ui.add(egui::Slider::new(&mut self.config.grid_size, 8..=256));
+                    ui.end_row();
+
+                    ui.label("Starting cells");
+                    ui.checkbox(&mut self.config.deployment, "Placed by hand, hidden from the others");
+                    ui.end_row();
+
+                    ui.label("Neighbourhood");
+                    egui::ComboBox::from_id_salt("landing_neighbourhood")
+                        .selected_text(self.config.neighbourhood.to_string())
+                        .show_ui(ui, |ui| {
+                            for neighbourhood in Neighbourhood::ALL {
+                                ui.selectable_value(
+                                    &mut self.config.neighbourhood,
+                                    neighbourhood,
+                                    neighbourhood.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Iterations");
+                    ui.add(egui::Slider::new(&mut self.config.max_iterations, 16..=1024));
+                    ui.end_row();
+
+                    ui.label("Victory");
+                    ui.horizontal(|ui| {
+                        let victory = &mut self.config.victory;
+                        // Rounds carry over between the round-based conditions
+                        let rounds = match *victory {
+                            VictoryCondition::TotalCells(_) => ROUNDS_TO_WIN,
+                            condition => condition.target(),
+                        };
+                        for (condition, label) in [
+                            (VictoryCondition::FirstToWins(rounds), "First to N wins"),
+                            (VictoryCondition::FixedRounds(rounds), "Most wins in N rounds"),
+                            (VictoryCondition::TotalCells(VICTORY_CELLS), "First to N total cells"),
+                        ] {
+                            let selected = std::mem::discriminant(victory)
+                                == std::mem::discriminant(&condition);
+                            if ui.radio(selected, label).clicked() && !selected {
+                                *victory = condition;
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("N");
+                    let mut n = self.config.victory.target();
+                    let cells = matches!(self.config.victory, VictoryCondition::TotalCells(_));
+                    let range = if cells { 100..=100_000 } else { 1..=20 };
+                    ui.add(egui::Slider::new(&mut n, range).logarithmic(cells));
+                    self.set_target(n);
+                    ui.end_row();
+
+                    ui.label("Conflicts");
+                    egui::ComboBox::from_id_salt("landing_conflict")
+                        .selected_text(self.config.conflict.to_string())
+                        .show_ui(ui, |ui| {
+                            for conflict in ConflictResolution::ALL {
+                                ui.selectable_value(
+                                    &mut self.config.conflict,
+                                    conflict,
+                                    conflict.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Seed");
+                    ui.add(egui::TextEdit::singleline(&mut self.seed).hint_text("random"));
+                    ui.end_row();
+                });
+
+            ui.add_space(10.0);
+            ui.separator();
+            ui.add_space(10.0);
+
+            if ui
+                .selectable_label(self.show_help, "How to play")
+                .clicked()
+            {
+                self.show_help = !self.show_help;
+            }
+            if self.show_help {
+                ui.add_space(5.0);
+                ui.label(format!(
+                    "{players} players fight over a {size}x{size} wrapping grid of cells. \
+                     Each cell is neutral, blue (Player 1), red (Player 2), \
+                     green (Player 3) or orange (Player 4).",
+                    players = self.config.nb_players(),
+                    size = self.config.grid_size
+                ));
+                if self.config.is_teams() {
+                    ui.label(
+                        "2v2: players 1 and 3 team up against players 2 and 4, \
+                         a team wins a round with the most cells in total.",
+                    );
+                }
+                ui.label(match self.config.neighbourhood {
+                    Neighbourhood::VonNeumann => {
+                        "A rule is a pattern of the 4 neighbours of a cell (top, right, bottom, left). \
+                         At every iteration, a cell whose neighbours match one of your rules becomes yours."
+                    }
+                    Neighbourhood::Moore => {
+                        "A rule is a pattern of the 8 cells around a cell, corners included. \
+                         At every iteration, a cell whose neighbours match one of your rules becomes yours."
+                    }
+                    Neighbourhood::Hex => {
+                        "Cells are hexagons, and a rule is a pattern of their 6 neighbours. \
+                         At every iteration, a cell whose neighbours match one of your rules becomes yours."
+                    }
+                });
+                ui.label(format!(
+                    "1. Players take turns picking {} starting rules.",
+                    self.config.player_start_rules
+                ));
+                ui.label(format!(
+                    "2. Battle: the grid evolves for up to {} iterations, \
+                     the player owning the most cells wins the round.",
+                    self.config.max_iterations
+                ));
+                ui.label(
+                    "3. Shop: the losers shop first. Buy rules, delete your rules or \
+                     your opponents', or upgrade your spawn probability.",
+                );
+                ui.label(format!(
+                    "When several players' rules match the same cell: {}.",
+                    self.config.conflict
+                ));
+                ui.label(format!("Victory: {}.", self.config.victory));
+
+                ui.add_space(10.0);
+                components::text::heading_small(ui, "Rule lookup");
+                let rule = components::rule_entry::show(ui, &mut self.rule_text);
+                if let Some(Rule::Pattern(pattern)) = rule
+                    && pattern.neighbourhood() != self.config.neighbourhood
+                {
+                    ui.label(format!(
+                        "Only matches with the {} neighbourhood.",
+                        pattern.neighbourhood()
+                    ));
+                }
+            }
+        });
+
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_landing_settings_map_to_match() {
+        let mut landing = LandingScreen::default();
+        landing.set_preset(Preset::Quick);
+        assert_eq!(landing.config, Preset::Quick.config());
+        landing.config.players = 3;
+        landing.config.grid_size = 48;
+        landing.config.max_iterations = 200;
+        landing.config.victory = VictoryCondition::FixedRounds(3);
+        landing.set_target(7);
+        landing.versus_ai = Some(Difficulty::Greedy);
+        landing.seed = " 42 ".to_string();
+
+        let engine = landing.new_match().unwrap();
+        let game = &engine.game;
+        assert_eq!(game.seed, 42);
+        assert_eq!(game.players.len(), 3);
+        assert_eq!((game.grid.width, game.grid.height), (48, 48));
+        assert_eq!(game.config.max_iterations, 200);
+        assert_eq!(game.config.victory, VictoryCondition::FixedRounds(7));
+
+        let bots = landing.bots(game.seed);
+        let players: Vec<u8> = bots.iter().map(|bot| bot.player).collect();
+        assert_eq!(players, [2, 3]);
+        assert!(bots.iter().all(|bot| bot.difficulty == Difficulty::Greedy));
+        landing.versus_ai = None;
+        assert!(landing.bots(game.seed).is_empty());
+
+        // Settings that cannot make a match are reported instead
+        landing.config.grid_size = 0;
+        assert!(landing.new_match().is_err());
+    }
+}
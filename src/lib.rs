@@ -0,0 +1,28 @@
+pub mod ai;
+pub mod components;
+pub mod config;
+pub mod constants;
+pub mod deployment;
+pub mod end_screen;
+pub mod engine;
+pub mod game;
+pub mod grid;
+pub mod landing;
+pub mod playback;
+pub mod player;
+pub mod replay;
+pub mod rule;
+pub mod rule_picker;
+pub mod sandbox;
+pub mod save;
+pub mod shop;
+
+pub use constants::*;
+pub use engine::GameEngine;
+pub use game::Game;
+pub use grid::Grid;
+pub use grid::cell::CellState;
+pub use player::Player;
+pub use rule::Rule;
+pub use rule_picker::RulePicker;
+pub use shop::Shop;
@@ -1,163 +1,372 @@
-pub mod components;
-pub mod constants;
-pub mod game;
-pub mod grid;
-pub mod player;
-pub mod rule;
-pub mod rule_picker;
-pub mod shop;
-
-pub use constants::*;
-pub use game::Game;
-pub use grid::Grid;
-pub use grid::cell::CellState;
-pub use player::Player;
-pub use rule::Rule;
-pub use rule_picker::RulePicker;
-pub use shop::Shop;
+use toom_war::ai::{Bot, Difficulty, Thinking};
+use toom_war::config::GameConfig;
+use toom_war::constants;
+use toom_war::deployment::DeploymentAction;
+use toom_war::end_screen::{self, EndAction};
+use toom_war::engine::{GameEngine, Phase};
+use toom_war::landing::{LandingAction, LandingScreen};
+use toom_war::playback::Playback;
+use toom_war::replay::ReplayViewer;
+use toom_war::sandbox::Sandbox;
+use toom_war::save;
 
 use eframe::egui;
-use std::time::{Duration, Instant};
-
-pub use constants::SHOP_NB_RULES;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
+#[derive(Serialize, Deserialize)]
 pub enum Page {
     LandingScreen,
     InitialRulePicker,
+    Deployment,
     MainGame,
     Shop,
+    Replay,
     EndScreen,
+    Sandbox,
 }
 
+/// The whole UI state is the save file: engine plus the page being shown.
+#[derive(Serialize, Deserialize)]
 struct GameUI {
-    game: Game,
-    last_update: Instant,
+    engine: GameEngine,
+    /// Pace of the battle, replays and sandbox
+    #[serde(skip)]
+    playback: Playback,
     current_page: Page,
-    rule_picker: RulePicker,
+    /// Computer players controlling every player but Player 1, if playing against the AI
+    bots: Vec<Bot>,
+    /// Bot deciding its turn on a worker thread
+    #[serde(skip)]
+    thinking: Option<Thinking>,
+    #[serde(skip)]
+    replay: Option<ReplayViewer>,
+    #[serde(skip)]
+    sandbox: Option<Sandbox>,
+    #[serde(skip)]
+    landing: LandingScreen,
 }
 
-impl Default for GameUI {
-    fn default() -> Self {
-        let mut game = Game::new();
-        game.new_round();
+impl GameUI {
+    /// Open the landing screen, prefilled with the command-line settings.
+    fn new(config: GameConfig, seed: Option<u64>, ai: Option<Difficulty>) -> Self {
+        let landing = LandingScreen {
+            config: config.clone(),
+            versus_ai: ai,
+            seed: seed.map(|seed| seed.to_string()).unwrap_or_default(),
+            ..Default::default()
+        };
+        Self {
+            engine: GameEngine::new(config),
+            playback: Playback::default(),
+            current_page: Page::LandingScreen,
+            bots: Vec::new(),
+            thinking: None,
+            replay: None,
+            sandbox: None,
+            landing,
+        }
+    }
 
-        game.player1
-            .rules
-            .push(Rule::new_direction(CellState::Player1));
+    /// Start a new match with the settings chosen on the landing screen.
+    fn start_match(&mut self) {
+        self.engine = match self.landing.new_match() {
+            Ok(engine) => engine,
+            Err(e) => {
+                self.landing.error = Some(e.to_string());
+                return;
+            }
+        };
+        self.bots = self.landing.bots(self.engine.game.seed);
+        self.thinking = None;
+        self.replay = None;
+        self.current_page = Page::InitialRulePicker;
+    }
 
-        game.player2
-            .rules
-            .push(Rule::new_direction(CellState::Player2));
+    /// Play again with the same settings and opponent, on a new seed.
+    fn rematch(&mut self) {
+        self.landing.config = self.engine.game.config.clone();
+        self.landing.versus_ai = self.bots.first().map(|bot| bot.difficulty);
+        self.landing.seed.clear();
+        self.start_match();
+    }
 
-        let rule_picker = RulePicker::new_with_players(&game.player1, &game.player2);
+    /// Let the bot whose turn it is decide on a worker thread, and play its decision once taken. \
+    /// Returns the player of the bot still deciding, if any.
+    fn play_bots(&mut self, ctx: &egui::Context) -> Option<u8> {
+        if let Some(thinking) = self.thinking.take_if(|thinking| thinking.is_finished()) {
+            let (bot, decision) = thinking.join();
+            if let Some(decision) = decision {
+                bot.play(&mut self.engine, decision);
+            }
+            if let Some(slot) = self.bots.iter_mut().find(|b| b.player == bot.player) {
+                *slot = bot;
+            }
+        }
+        if self.thinking.is_none() {
+            self.thinking = self
+                .bots
+                .iter()
+                .find(|bot| bot.has_turn(&self.engine))
+                .map(|bot| Thinking::start(bot, &self.engine));
+        }
+        let player = self.thinking.as_ref().map(|thinking| thinking.player);
+        if player.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        player
+    }
 
-        Self {
-            game,
-            last_update: Instant::now(),
-            current_page: Page::InitialRulePicker,
-            rule_picker,
+    fn autosave(&self) {
+        if matches!(self.current_page, Page::LandingScreen | Page::Sandbox) {
+            return;
+        }
+        if let Err(e) = save::save_to_file(self, constants::SAVE_FILE) {
+            eprintln!("Auto-save failed: {}", e);
         }
     }
 }
 
 impl eframe::App for GameUI {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::S)) {
+            self.autosave();
+        }
+
         match &self.current_page {
-            Page::LandingScreen => todo!(),
+            Page::LandingScreen => {
+                let can_continue = std::path::Path::new(constants::SAVE_FILE).exists();
+                let mut action = None;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    action = self.landing.show(ui, can_continue);
+                });
+
+                match action {
+                    Some(LandingAction::NewMatch) => self.start_match(),
+                    Some(LandingAction::ContinueMatch) => {
+                        match save::load_from_file::<GameUI>(constants::SAVE_FILE) {
+                            Ok(loaded) => *self = loaded,
+                            Err(e) => self.landing.error = Some(e.to_string()),
+                        }
+                    }
+                    Some(LandingAction::Sandbox) => {
+                        self.sandbox = Some(Sandbox::new(self.landing.config.clone()));
+                        self.current_page = Page::Sandbox;
+                    }
+                    None => {}
+                }
+            }
 
             Page::InitialRulePicker => {
-                let player = if self.rule_picker.player1_choosing {
-                    &mut self.game.player1
-                } else {
-                    &mut self.game.player2
-                };
+                let thinking = self.play_bots(ctx);
 
                 egui::CentralPanel::default().show(ctx, |ui| {
-                    self.rule_picker.show(ui, player);
+                    show_thinking(ui, thinking);
+                    let picked = ui
+                        .add_enabled_ui(thinking.is_none(), |ui| self.engine.rule_picker.show(ui))
+                        .inner;
+                    if let Some(index) = picked {
+                        self.engine.pick_rule(index);
+                    }
                 });
 
-                if self.game.player1.rules.len() == PLAYER_START_RULES
-                    && self.game.player2.rules.len() == PLAYER_START_RULES
-                {
+                match self.engine.phase {
+                    Phase::Deployment => self.current_page = Page::Deployment,
+                    Phase::Battle => self.current_page = Page::MainGame,
+                    _ => {}
+                }
+            }
+
+            Page::Deployment => {
+                for bot in &mut self.bots {
+                    bot.deploy(&mut self.engine);
+                }
+
+                let mut action = None;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    action = self.engine.deployment.show(ui, &self.engine.game.grid);
+                });
+                match action {
+                    Some(DeploymentAction::Place(index)) => {
+                        self.engine.deploy(index);
+                    }
+                    Some(DeploymentAction::Remove(index)) => {
+                        self.engine.undeploy(index);
+                    }
+                    Some(DeploymentAction::Done) => self.engine.end_deployment_turn(),
+                    None => {}
+                }
+
+                if self.engine.phase == Phase::Battle {
                     self.current_page = Page::MainGame;
+                    self.autosave();
                 }
             }
 
             Page::MainGame => {
-                if self.last_update.elapsed()
-                    >= Duration::from_millis(constants::UPDATE_INTERVAL_MS)
-                    && !self.game.round_over
-                {
-                    self.update_game();
-                    self.last_update = Instant::now();
+                if !self.engine.game.round_over {
+                    self.playback.handle_keys(ctx);
+                    let running = if self.playback.take_skip() {
+                        self.engine.run_round();
+                        false
+                    } else {
+                        (0..self.playback.take_steps()).all(|_| self.engine.step())
+                    };
+                    if !running {
+                        self.autosave();
+                    } else if !self.playback.paused {
+                        self.playback.request_repaint(ctx);
+                    }
+                    egui::TopBottomPanel::bottom("playback").show(ctx, |ui| {
+                        self.playback.show(ui);
+                    });
                 }
 
-                ctx.request_repaint_after(Duration::from_millis(100));
-
-                let mut shop_clicked = false;
+                let mut continue_clicked = false;
+                let mut replay_clicked = false;
                 egui::CentralPanel::default().show(ctx, |ui| {
-                    self.game.show(ui, &mut shop_clicked);
+                    self.engine
+                        .game
+                        .show(ui, &mut continue_clicked, &mut replay_clicked);
                 });
 
-                if shop_clicked {
-                    self.game.shop = Shop::new_with_players(&self.game.player1, &self.game.player2);
-                    self.game.shop.current_player = self.game.shop_first_player;
+                if continue_clicked && self.engine.phase == Phase::MatchOver {
+                    self.current_page = Page::EndScreen;
+                } else if continue_clicked {
+                    self.engine.open_shop();
                     self.current_page = Page::Shop;
+                } else if replay_clicked && !self.engine.game.history.is_empty() {
+                    self.replay = Some(ReplayViewer::new(self.engine.game.history.clone()));
+                    self.current_page = Page::Replay;
                 }
             }
             Page::Shop => {
+                let thinking = self.play_bots(ctx);
+
                 ctx.request_repaint_after(Duration::from_millis(100));
                 egui::CentralPanel::default().show(ctx, |ui| {
-                    let (player, opponent) = if self.game.shop.current_player == 1 {
-                        self.game.shop.player1_shopped = true;
-                        (&mut self.game.player1, &mut self.game.player2)
-                    } else {
-                        self.game.shop.player2_shopped = true;
-                        (&mut self.game.player2, &mut self.game.player1)
-                    };
-                    if self.game.shop.show(ui, player, opponent) {
-                        self.game.shop.current_player = if self.game.shop.current_player == 1 {
-                            2
-                        } else {
-                            1
-                        };
-
-                        if self.game.shop.player1_shopped && self.game.shop.player2_shopped {
-                            self.game.new_round();
-                            self.current_page = Page::MainGame;
-                        }
+                    show_thinking(ui, thinking);
+                    let (shop, players) = self.engine.shop_turn();
+                    let done = ui
+                        .add_enabled_ui(thinking.is_none(), |ui| shop.show(ui, players))
+                        .inner;
+                    if done {
+                        self.engine.end_shop_turn();
                     }
                 });
+
+                match self.engine.phase {
+                    Phase::Deployment => self.current_page = Page::Deployment,
+                    Phase::Battle => self.current_page = Page::MainGame,
+                    _ => {}
+                }
+                if !matches!(self.current_page, Page::Shop) {
+                    self.autosave();
+                }
             }
 
-            Page::EndScreen => todo!(),
-        }
-    }
-}
+            Page::Replay => {
+                let Some(replay) = &mut self.replay else {
+                    self.current_page = Page::MainGame;
+                    return;
+                };
 
-impl GameUI {
-    fn update_game(&mut self) {
-        // Update the grid with the rules of each player
-        self.game
-            .grid
-            .next(&self.game.player1.rules, &self.game.player2.rules);
-
-        // Check if grid is identical to previous state
-        let current_grid_state = self.game.grid.values.clone();
-        if let Some(ref previous_state) = self.game.previous_grid_state {
-            if *previous_state == current_grid_state {
-                self.game.end_round();
-                return;
+                if replay.playing {
+                    for _ in 0..self.playback.due() {
+                        replay.step_forward();
+                    }
+                    self.playback.request_repaint(ctx);
+                } else {
+                    self.playback.restart();
+                }
+
+                let mut close = false;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    close = replay.show(ui);
+                });
+
+                if close {
+                    self.replay = None;
+                    self.current_page = Page::MainGame;
+                }
+            }
+
+            Page::Sandbox => {
+                let Some(sandbox) = &mut self.sandbox else {
+                    self.current_page = Page::LandingScreen;
+                    return;
+                };
+
+                if sandbox.playing {
+                    for _ in 0..self.playback.due() {
+                        sandbox.step();
+                    }
+                    self.playback.request_repaint(ctx);
+                } else {
+                    self.playback.restart();
+                }
+
+                let mut close = false;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    close = sandbox.show(ui);
+                });
+
+                if close {
+                    self.sandbox = None;
+                    self.current_page = Page::LandingScreen;
+                }
+            }
+
+            Page::EndScreen => {
+                let mut action = None;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    action = end_screen::show(ui, &self.engine.game);
+                });
+
+                match action {
+                    Some(EndAction::Rematch) => self.rematch(),
+                    Some(EndAction::MainMenu) => self.current_page = Page::LandingScreen,
+                    None => {}
+                }
             }
         }
-        self.game.previous_grid_state = Some(current_grid_state);
+    }
+}
 
-        // Advance the iteration counter
-        self.game.advance_iteration();
+/// Spinner while the computer player `thinking` decides its turn.
+fn show_thinking(ui: &mut egui::Ui, thinking: Option<u8>) {
+    if let Some(player) = thinking {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label(format!("Player {} is thinking...", player));
+        });
     }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // `--seed <n>` replays a match exactly, `--load <file>` resumes a saved one,
+    // `--ai <random|greedy|lookahead>` makes every player but Player 1 a computer player,
+    // `--preset <quick|standard|marathon>`, `--config <file>` and `--set <field>=<value>` tune the match
+    let mut args = std::env::args().skip(1);
+    let mut config = GameConfig::default();
+    let mut seed = None;
+    let mut ai = None;
+    let mut loaded = None;
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            seed = Some(args.next().ok_or("missing value for --seed")?.parse()?);
+        } else if arg == "--ai" {
+            ai = Some(args.next().ok_or("missing value for --ai")?.parse()?);
+        } else if arg == "--load" {
+            let path = args.next().unwrap_or(constants::SAVE_FILE.to_string());
+            loaded = Some(save::load_from_file::<GameUI>(path)?);
+        } else if !config.parse_flag(&arg, &mut args)? {
+            return Err(format!("unknown argument: {}", arg).into());
+        }
+    }
+    config.validate()?;
+    let app = loaded.unwrap_or_else(|| GameUI::new(config, seed, ai));
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_title("NESW4: New Extreme Strategical Warfare 4"),
@@ -167,6 +376,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(eframe::run_native(
         "NESW4: New Extreme Strategical Warfare 4",
         options,
-        Box::new(|_cc| Ok(Box::<GameUI>::default())),
+        Box::new(move |_cc| Ok(Box::new(app))),
     )?)
 }
@@ -0,0 +1,234 @@
+use crate::constants::{
+    PLAYBACK_DEFAULT_SPEED, PLAYBACK_MAX_STEPS_PER_FRAME, PLAYBACK_SPEEDS, UPDATE_INTERVAL_MS,
+};
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+/// Pace of the simulation: paused, or one iteration every `UPDATE_INTERVAL_MS`
+/// divided by the speed. \
+/// Only decides when to step, so rounds play out the same at any speed.
+pub struct Playback {
+    pub paused: bool,
+    /// Index in `PLAYBACK_SPEEDS`
+    pub speed: usize,
+    /// Single iteration asked for while paused
+    step_requested: bool,
+    /// Rest of the round asked for at once
+    skip_requested: bool,
+    last_update: Instant,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            paused: false,
+            speed: PLAYBACK_DEFAULT_SPEED,
+            step_requested: false,
+            skip_requested: false,
+            last_update: Instant::now(),
+        }
+    }
+}
+
+impl Playback {
+    /// Speed multiplier, 1.0 being one iteration every `UPDATE_INTERVAL_MS`.
+    pub fn multiplier(&self) -> f32 {
+        PLAYBACK_SPEEDS[self.speed]
+    }
+
+    /// Time between two iterations.
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(UPDATE_INTERVAL_MS).div_f32(self.multiplier())
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.last_update = Instant::now();
+    }
+
+    /// Pause and play a single iteration.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step_requested = true;
+    }
+
+    /// Play the rest of the round at once.
+    pub fn skip(&mut self) {
+        self.skip_requested = true;
+    }
+
+    /// Whether the rest of the round was asked for since the last call.
+    pub fn take_skip(&mut self) -> bool {
+        std::mem::take(&mut self.skip_requested)
+    }
+
+    /// Iterations to play this frame: those due at the current speed,
+    /// or the single one asked for while paused.
+    pub fn take_steps(&mut self) -> usize {
+        if self.paused {
+            self.last_update = Instant::now();
+            return std::mem::take(&mut self.step_requested) as usize;
+        }
+        self.due()
+    }
+
+    /// Iterations due at the current speed since the last call, whether paused or not. \
+    /// After a stall longer than `PLAYBACK_MAX_STEPS_PER_FRAME` iterations,
+    /// e.g. while on another page, a single one is due.
+    pub fn due(&mut self) -> usize {
+        let interval = self.interval();
+        let due = (self.last_update.elapsed().as_secs_f64() / interval.as_secs_f64()) as usize;
+        if due > PLAYBACK_MAX_STEPS_PER_FRAME {
+            self.last_update = Instant::now();
+            return 1;
+        }
+        self.last_update += interval * due as u32;
+        due
+    }
+
+    /// Restart the timer, so nothing is due until a full interval has passed.
+    pub fn restart(&mut self) {
+        self.last_update = Instant::now();
+    }
+
+    /// Repaint when the next iteration is due.
+    pub fn request_repaint(&self, ctx: &egui::Context) {
+        let next = self.interval().saturating_sub(self.last_update.elapsed());
+        ctx.request_repaint_after(next);
+    }
+
+    /// Keyboard shortcuts: Space pauses or resumes, Right arrow steps, Up and Down arrows
+    /// (or + and -) change the speed, End skips to the result. \
+    /// Ignored while typing in a text field, and consumed otherwise,
+    /// so Space does not also press the focused button.
+    pub fn handle_keys(&mut self, ctx: &egui::Context) {
+        let focused = ctx.memory(|m| m.focused());
+        if focused.is_some_and(|id| egui::TextEdit::load_state(ctx, id).is_some()) {
+            return;
+        }
+        ctx.input_mut(|i| {
+            let mut pressed = |key| i.consume_key(egui::Modifiers::NONE, key);
+            if pressed(egui::Key::Space) {
+                self.toggle_pause();
+            }
+            if pressed(egui::Key::ArrowRight) {
+                self.step();
+            }
+            if pressed(egui::Key::ArrowUp) | pressed(egui::Key::Plus) {
+                self.faster();
+            }
+            if pressed(egui::Key::ArrowDown) | pressed(egui::Key::Minus) {
+                self.slower();
+            }
+            if pressed(egui::Key::End) {
+                self.skip();
+            }
+        });
+    }
+
+    /// Pause, step, speed and skip controls.
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let pause_text = if self.paused { "Resume" } else { "Pause" };
+            if ui.button(pause_text).on_hover_text("Space").clicked() {
+                self.toggle_pause();
+            }
+            if ui.button("Step").on_hover_text("Right arrow").clicked() {
+                self.step();
+                // Play it right away, even without further input
+                ui.ctx().request_repaint();
+            }
+            ui.separator();
+            if ui.button("-").on_hover_text("Down arrow").clicked() {
+                self.slower();
+            }
+            ui.label(egui::RichText::new(format!("{}x", self.multiplier())).monospace());
+            if ui.button("+").on_hover_text("Up arrow").clicked() {
+                self.faster();
+            }
+            ui.separator();
+            if ui.button("Skip to result").on_hover_text("End").clicked() {
+                self.skip();
+                ui.ctx().request_repaint();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playback_controls() {
+        let mut playback = Playback::default();
+        assert_eq!(playback.multiplier(), 1.0);
+        for _ in 0..PLAYBACK_SPEEDS.len() {
+            playback.faster();
+        }
+        assert_eq!(playback.multiplier(), 16.0);
+        assert_eq!(playback.interval(), Duration::from_micros(6250));
+
+        // Paused, only the requested iteration is played
+        playback.step();
+        assert!(playback.paused);
+        assert_eq!(playback.take_steps(), 1);
+        assert_eq!(playback.take_steps(), 0);
+
+        playback.skip();
+        assert!(playback.take_skip());
+        assert!(!playback.take_skip());
+    }
+
+    #[test]
+    fn test_playback_keys() {
+        let ctx = egui::Context::default();
+        let space = || egui::RawInput {
+            events: vec![egui::Event::Key {
+                key: egui::Key::Space,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: egui::Modifiers::NONE,
+            }],
+            ..Default::default()
+        };
+        let mut playback = Playback::default();
+        let mut text = String::new();
+        let mut frame = |input, focus_text: bool, playback: &mut Playback| {
+            let mut clicked = false;
+            let _ = ctx.run(input, |ctx| {
+                playback.handle_keys(ctx);
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    let button = ui.button("Pause");
+                    clicked = button.clicked();
+                    let edit = ui.text_edit_singleline(&mut text);
+                    if focus_text {
+                        edit.request_focus();
+                    } else {
+                        button.request_focus();
+                    }
+                });
+            });
+            clicked
+        };
+
+        // Space toggles the pause, without pressing the focused button
+        frame(egui::RawInput::default(), false, &mut playback);
+        assert!(!frame(space(), false, &mut playback));
+        assert!(playback.paused);
+
+        // Space typed in a text field is not a shortcut
+        frame(egui::RawInput::default(), true, &mut playback);
+        frame(space(), true, &mut playback);
+        assert!(playback.paused);
+    }
+}
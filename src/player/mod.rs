@@ -1,9 +1,10 @@
-use crate::CELL_SIZE;
 use crate::components;
-use crate::constants::{PLAYER_MAX_RULES, PLAYER_SPAWN_PROBA, PLAYER_START_MONEY};
-use crate::rule::Rule;
+use crate::config::GameConfig;
+use crate::rule::{self, Rule};
 use eframe::egui;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub name: String,
     pub rules: Vec<Rule>,
@@ -11,21 +12,78 @@ pub struct Player {
     pub spawn_proba: f32, // spawn probability
     pub money: usize,
     pub win: usize,
+    /// Cells owned at the end of each round, summed
+    pub total_cells: usize,
+    pub money_spent: usize,
+    /// Cells captured by each rule this round, in rule order
+    #[serde(default)]
+    pub rule_captures: Vec<usize>,
+    /// Cells captured by each rule over the whole match, in rule order
+    #[serde(default)]
+    pub rule_lifetime: Vec<usize>,
 }
 
 impl Player {
-    pub fn new(name: String) -> Player {
+    pub fn new(name: String, config: &GameConfig) -> Player {
         Player {
             name,
             rules: Vec::new(),
-            max_rules: PLAYER_MAX_RULES,
-            spawn_proba: PLAYER_SPAWN_PROBA,
-            money: PLAYER_START_MONEY,
+            max_rules: config.player_max_rules,
+            spawn_proba: config.player_spawn_proba,
+            money: config.player_start_money,
             win: 0,
+            total_cells: 0,
+            money_spent: 0,
+            rule_captures: Vec::new(),
+            rule_lifetime: Vec::new(),
         }
     }
 
-    pub fn show(&self, ui: &mut egui::Ui, cell_count: usize) {
+    /// Count a cell captured by the rule at `index`.
+    pub fn record_capture(&mut self, index: usize) {
+        for captures in [&mut self.rule_captures, &mut self.rule_lifetime] {
+            if captures.len() <= index {
+                captures.resize(index + 1, 0);
+            }
+            captures[index] += 1;
+        }
+    }
+
+    /// Cells captured by the rule at `index` this round (or the last one while shopping),
+    /// and over the whole match.
+    pub fn rule_stats(&self, index: usize) -> (usize, usize) {
+        let get = |captures: &[usize]| captures.get(index).copied().unwrap_or(0);
+        (get(&self.rule_captures), get(&self.rule_lifetime))
+    }
+
+    /// Remove the rule at `index` along with its statistics.
+    pub fn remove_rule(&mut self, index: usize) -> Rule {
+        for captures in [&mut self.rule_captures, &mut self.rule_lifetime] {
+            if index < captures.len() {
+                captures.remove(index);
+            }
+        }
+        self.rules.remove(index)
+    }
+
+    /// Statistics of the rule at `index`, to show under its card.
+    pub fn show_rule_stats(&self, ui: &mut egui::Ui, index: usize) {
+        let (round, lifetime) = self.rule_stats(index);
+        ui.label(egui::RichText::new(format!("+{} / {}", round, lifetime)).monospace())
+            .on_hover_text(format!(
+                "Captured {} cells last round, {} over the match",
+                round, lifetime
+            ));
+    }
+
+    /// Show the stats and rules of the player, framing the rule `selected`. \
+    /// Returns the rule under the pointer and the rule clicked this frame, if any.
+    pub fn show(
+        &self,
+        ui: &mut egui::Ui,
+        cell_count: usize,
+        selected: Option<usize>,
+    ) -> (Option<usize>, Option<usize>) {
         ui.label(egui::RichText::new(format!("Score: {}", self.win)).monospace());
         ui.label(egui::RichText::new(format!("Cells: {}", cell_count)).monospace());
         ui.label(egui::RichText::new(format!("Money: {}", self.money)).monospace());
@@ -41,19 +99,25 @@ impl Player {
             self.max_rules
         )));
         ui.add_space(5.0);
-        egui::Grid::new(format!("rules_grid {}", self.name))
-            .num_columns(2)
-            .max_col_width(CELL_SIZE * 3.0 + 10.0)
-            .spacing([10.0, 10.0])
-            .show(ui, |ui| {
-                for (i, rule) in self.rules.iter().enumerate() {
-                    ui.vertical_centered(|ui| {
-                        rule.show(ui);
-                    });
-                    if (i + 1) % 2 == 0 {
-                        ui.end_row();
-                    }
-                }
-            });
+        rule::show_rules_captures(
+            ui,
+            format!("rules_grid {}", self.name),
+            &self.rules,
+            &self.rule_captures,
+            selected,
+        )
+    }
+}
+
+/// Mutable references to players `a` and `b` (numbered from 1) of `players`. \
+/// `a` and `b` must be different.
+pub fn two_mut(players: &mut [Player], a: u8, b: u8) -> (&mut Player, &mut Player) {
+    let (a, b) = (a as usize - 1, b as usize - 1);
+    if a < b {
+        let (left, right) = players.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = players.split_at_mut(a);
+        (&mut right[0], &mut left[b])
     }
 }
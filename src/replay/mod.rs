@@ -0,0 +1,258 @@
+use crate::components;
+use crate::config::ConflictResolution;
+use crate::grid::Grid;
+use crate::grid::cell::CellState;
+use crate::grid::table::{self, OutcomeTable};
+use crate::rule::{self, Rule};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Everything needed to replay a round. \
+/// Grid evolution is deterministic, so the starting grid, both rule sets
+/// and the conflict resolution are enough to rebuild every frame.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoundRecord {
+    pub seed: u64,
+    pub round: usize,
+    /// Rules of each player, in player order
+    pub rules: Vec<Vec<Rule>>,
+    #[serde(default)]
+    pub conflict: ConflictResolution,
+    pub initial_grid: Grid,
+    /// Number of grid updates played during the round.
+    pub steps: usize,
+    /// Cells owned by each player when the round ended.
+    pub cells: Vec<usize>,
+    /// Rounds won by each player so far, once the round ended.
+    #[serde(default)]
+    pub wins: Vec<usize>,
+    /// Money of each player once the round ended.
+    #[serde(default)]
+    pub money: Vec<usize>,
+}
+
+impl RoundRecord {
+    /// Every grid of the round, from the initial one to the final one.
+    pub fn frames(&self) -> Vec<Grid> {
+        let mut frames = Vec::with_capacity(self.steps + 1);
+        self.for_each_frame(|grid| frames.push(grid.clone()));
+        frames
+    }
+
+    /// Call `f` on every grid of the round in turn, from the initial one to the final one.
+    pub fn for_each_frame(&self, mut f: impl FnMut(&Grid)) {
+        let mut grid = self.initial_grid.clone();
+        f(&grid);
+        if self.steps == 0 {
+            return;
+        }
+        let rules: Vec<&[Rule]> = self.rules.iter().map(Vec::as_slice).collect();
+        let mut table =
+            OutcomeTable::with_conflict(&rules, self.initial_grid.neighbourhood, self.conflict);
+        for i in 0..self.steps {
+            table.set_step(i, table::step_salt(self.seed, self.round, i));
+            grid.next_with_table(&table, 1);
+            f(&grid);
+        }
+    }
+}
+
+/// Frame-by-frame viewer over the recorded rounds of a match.
+pub struct ReplayViewer {
+    pub rounds: Vec<RoundRecord>,
+    pub selected: usize,
+    pub frames: Vec<Grid>,
+    pub frame: usize,
+    pub playing: bool,
+}
+
+impl ReplayViewer {
+    /// Open the viewer on the last recorded round. `rounds` should not be empty.
+    pub fn new(rounds: Vec<RoundRecord>) -> ReplayViewer {
+        let mut viewer = ReplayViewer {
+            rounds,
+            selected: 0,
+            frames: Vec::new(),
+            frame: 0,
+            playing: false,
+        };
+        viewer.select(viewer.rounds.len().saturating_sub(1));
+        viewer
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if let Some(record) = self.rounds.get(index) {
+            self.selected = index;
+            self.frames = record.frames();
+            self.frame = 0;
+            self.playing = false;
+        }
+    }
+
+    pub fn last_frame(&self) -> usize {
+        self.frames.len().saturating_sub(1)
+    }
+
+    /// Move to the next frame, pausing on the last one.
+    pub fn step_forward(&mut self) {
+        if self.frame < self.last_frame() {
+            self.frame += 1;
+        }
+        if self.frame == self.last_frame() {
+            self.playing = false;
+        }
+    }
+
+    pub fn step_back(&mut self) {
+        self.frame = self.frame.saturating_sub(1);
+    }
+
+    /// Returns `true` when the viewer should be closed.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut close_clicked = false;
+        components::text::title(ui);
+
+        let Some(record) = self.rounds.get(self.selected) else {
+            return true;
+        };
+        let (round, seed) = (record.round, record.seed);
+        let rules = record.rules.clone();
+
+        ui.horizontal(|ui| {
+            components::text::heading(ui, format!("Replay - Round {} (seed {})", round, seed));
+            let mut selected = self.selected;
+            egui::ComboBox::from_id_salt("replay_round")
+                .selected_text(format!("Round {}", round))
+                .show_ui(ui, |ui| {
+                    for (i, round) in self.rounds.iter().enumerate() {
+                        ui.selectable_value(&mut selected, i, format!("Round {}", round.round));
+                    }
+                });
+            if selected != self.selected {
+                self.select(selected);
+            }
+            if ui.button("Back").clicked() {
+                close_clicked = true;
+            }
+        });
+
+        let available_rect = ui.available_rect_before_wrap();
+        if available_rect.width().is_nan() || available_rect.width() <= 0.0 {
+            return close_clicked;
+        }
+
+        let total_width = available_rect.width();
+        let side_width = total_width / 5.0;
+        let grid_section_width = total_width * 3.0 / 5.0;
+
+        let bar_height = 50.0;
+        let mut view_rect = available_rect;
+        view_rect.max.y -= bar_height;
+        let bar_rect = egui::Rect::from_min_max(
+            egui::pos2(available_rect.min.x, view_rect.max.y),
+            available_rect.max,
+        );
+
+        ui.scope_builder(egui::UiBuilder::new().max_rect(view_rect), |ui| {
+            ui.horizontal(|ui| {
+                ui.scope(|ui| {
+                    ui.set_min_width(side_width);
+                    ui.set_max_width(side_width);
+                    show_rules_column(ui, &rules, 1);
+                });
+
+                ui.scope(|ui| {
+                    ui.set_min_width(grid_section_width);
+                    ui.set_max_width(grid_section_width);
+                    ui.vertical_centered(|ui| {
+                        let last_frame = self.last_frame();
+                        ui.horizontal(|ui| {
+                            if ui.button("Step back").clicked() {
+                                self.playing = false;
+                                self.step_back();
+                            }
+                            let play_label = if self.playing { "Pause" } else { "Play" };
+                            if ui.button(play_label).clicked() {
+                                if !self.playing && self.frame == last_frame {
+                                    self.frame = 0;
+                                }
+                                self.playing = !self.playing;
+                            }
+                            if ui.button("Step").clicked() {
+                                self.playing = false;
+                                self.step_forward();
+                            }
+                            ui.add(
+                                egui::Slider::new(&mut self.frame, 0..=last_frame)
+                                    .text(format!("/ {}", last_frame)),
+                            );
+                        });
+                        ui.add_space(5.0);
+
+                        self.frames[self.frame].show(ui);
+                    });
+                });
+
+                ui.scope(|ui| {
+                    ui.set_min_width(side_width);
+                    ui.set_max_width(side_width);
+                    show_rules_column(ui, &rules, 0);
+                });
+            });
+        });
+
+        ui.scope_builder(egui::UiBuilder::new().max_rect(bar_rect), |ui| {
+            components::score_bar::show(ui, grid_section_width, &self.frames[self.frame]);
+        });
+
+        close_clicked
+    }
+}
+
+/// Rules of the odd (`parity` 1) or even (`parity` 0) players, as in the battle view.
+fn show_rules_column(ui: &mut egui::Ui, rules: &[Vec<Rule>], parity: usize) {
+    ui.vertical(|ui| {
+        for (i, rules) in rules.iter().enumerate() {
+            if (i + 1) % 2 != parity {
+                continue;
+            }
+            ui.heading(
+                egui::RichText::new(format!("Player {}", i + 1))
+                    .color(CellState::player(i as u8 + 1).color())
+                    .size(18.0)
+                    .strong(),
+            );
+            ui.add_space(10.0);
+            rule::show_rules(ui, ("replay_rules", i), rules);
+            ui.add_space(20.0);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ConflictResolution, GameConfig};
+    use crate::engine::{GameEngine, Phase};
+
+    #[test]
+    fn test_replay_matches_played_round() {
+        for conflict in [ConflictResolution::Player1First, ConflictResolution::Random] {
+            let config = GameConfig {
+                conflict,
+                ..GameConfig::default()
+            };
+            let mut engine = GameEngine::new_with_seed(config, 3);
+            let mut index = 0;
+            while engine.phase == Phase::RulePicking {
+                engine.pick_rule(index);
+                index += 1;
+            }
+            engine.run_round();
+
+            let record = engine.game.history.last().unwrap();
+            let frames = record.frames();
+            assert_eq!(frames.len(), record.steps + 1);
+            assert_eq!(frames.last().unwrap().values, engine.game.grid.values);
+        }
+    }
+}
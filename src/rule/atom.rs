@@ -0,0 +1,103 @@
+use crate::grid::cell::{CellState, hexagon};
+use crate::{COLOR_GRID_LINE, COLOR_WILDCARD};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// What one neighbour of a rule accepts. \
+/// `Mine` and `Opponent` are relative to the player owning the rule.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Atom {
+    /// Any cell
+    Any,
+    /// Any player's cell
+    Occupied,
+    /// A cell of the rule's owner
+    Mine,
+    /// A cell of another player
+    Opponent,
+    /// Any cell but this state
+    Not(CellState),
+    /// Exactly this state. Serialized as the bare state, like rules before wildcards.
+    #[serde(untagged)]
+    Cell(CellState),
+}
+
+impl Atom {
+    /// Whether `cell` matches, for a rule owned by `owner` (1 to 4).
+    #[inline]
+    pub fn matches(self, owner: u8, cell: CellState) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Occupied => cell != CellState::Neutral,
+            Atom::Mine => cell.owner() == Some(owner),
+            Atom::Opponent => cell.owner().is_some_and(|p| p != owner),
+            Atom::Not(state) => cell != state,
+            Atom::Cell(state) => cell == state,
+        }
+    }
+
+    /// Fill color and glyph, no glyph for an exact state.
+    fn look(self) -> (egui::Color32, &'static str) {
+        match self {
+            Atom::Cell(state) => (state.color(), ""),
+            Atom::Not(state) => (state.color(), "!"),
+            Atom::Any => (COLOR_WILDCARD, "*"),
+            Atom::Occupied => (COLOR_WILDCARD, "+"),
+            Atom::Mine => (COLOR_WILDCARD, "M"),
+            Atom::Opponent => (COLOR_WILDCARD, "O"),
+        }
+    }
+
+    pub fn show(self, painter: &egui::Painter, rect: egui::Rect) {
+        let (fill, glyph) = self.look();
+        painter.rect_filled(rect, 0.0, fill);
+        painter.rect_stroke(
+            rect,
+            0.0,
+            egui::Stroke::new(0.5, COLOR_GRID_LINE),
+            egui::StrokeKind::Inside,
+        );
+        show_glyph(painter, rect.center(), rect.height(), glyph);
+    }
+
+    /// Paint the atom as a pointy-top hexagon.
+    pub fn show_hex(self, painter: &egui::Painter, center: egui::Pos2, radius: f32) {
+        let (fill, glyph) = self.look();
+        painter.add(egui::Shape::convex_polygon(
+            hexagon(center, radius),
+            fill,
+            egui::Stroke::new(0.5, COLOR_GRID_LINE),
+        ));
+        show_glyph(painter, center, radius * 1.5, glyph);
+    }
+}
+
+fn show_glyph(painter: &egui::Painter, center: egui::Pos2, size: f32, glyph: &str) {
+    painter.text(
+        center,
+        egui::Align2::CENTER_CENTER,
+        glyph,
+        egui::FontId::monospace(size * 0.8),
+        egui::Color32::BLACK,
+    );
+}
+
+impl From<CellState> for Atom {
+    fn from(state: CellState) -> Atom {
+        Atom::Cell(state)
+    }
+}
+
+/// The state code for exact cells, `*`, `+`, `m`, `o` or `!` and a state code for wildcards.
+impl std::fmt::Display for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Atom::Any => write!(f, "*"),
+            Atom::Occupied => write!(f, "+"),
+            Atom::Mine => write!(f, "m"),
+            Atom::Opponent => write!(f, "o"),
+            Atom::Not(state) => write!(f, "!{}", state.code()),
+            Atom::Cell(state) => write!(f, "{}", state.code()),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::CELL_SIZE;
+use crate::grid::cell::CellState;
+use crate::rule::atom::Atom;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Outer-totalistic rule: only counts the owner's and the other players' cells
+/// among the neighbours, wherever they are.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CountRule {
+    /// Fewest neighbours owned by the rule's owner, at least 1
+    pub min_mine: u8,
+    /// Most neighbours owned by other players
+    pub max_opponent: u8,
+}
+
+impl CountRule {
+    /// Whether the rule, owned by player `owner`, matches the neighbours.
+    pub fn matches(&self, owner: u8, cells: &[CellState]) -> bool {
+        let mine = cells.iter().filter(|c| c.owner() == Some(owner)).count();
+        let opponent = cells
+            .iter()
+            .filter(|c| c.owner().is_some_and(|p| p != owner))
+            .count();
+        self.matches_counts(mine, opponent)
+    }
+
+    /// Whether the rule matches `mine` neighbours of its owner and `opponent` of other players.
+    pub fn matches_counts(&self, mine: usize, opponent: usize) -> bool {
+        mine >= self.min_mine as usize && opponent <= self.max_opponent as usize
+    }
+
+    /// Number of neighbourhoods of `arity` cells over `nb_players` players and neutral matched:
+    /// for each count of own and opponent cells, the ways to place them and to pick the opponents.
+    pub fn coverage(&self, nb_players: usize, arity: usize) -> usize {
+        let binomial = |n: usize, k: usize| (0..k).fold(1, |b, i| b * (n - i) / (i + 1));
+        (self.min_mine as usize..=arity)
+            .map(|mine| {
+                (0..=(self.max_opponent as usize).min(arity - mine))
+                    .map(|opponent| {
+                        binomial(arity, mine)
+                            * binomial(arity - mine, opponent)
+                            * (nb_players - 1).pow(opponent as u32)
+                    })
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// Paint the counts in the 3x3 cells square starting at `origin`:
+    /// the `Mine` atom with its minimum on top, the `Opponent` atom with its maximum below.
+    pub fn show(&self, painter: &egui::Painter, origin: egui::Pos2) {
+        let lines = [
+            (0.0, Atom::Mine, format!(">={}", self.min_mine)),
+            (2.0, Atom::Opponent, format!("<={}", self.max_opponent)),
+        ];
+        for (row, atom, text) in lines {
+            let y = origin.y + row * CELL_SIZE;
+            let icon = egui::Rect::from_min_size(
+                egui::pos2(origin.x, y),
+                egui::vec2(CELL_SIZE, CELL_SIZE),
+            );
+            atom.show(painter, icon);
+            painter.text(
+                egui::pos2(origin.x + CELL_SIZE * 2.0, y + CELL_SIZE / 2.0),
+                egui::Align2::CENTER_CENTER,
+                text,
+                egui::FontId::monospace(CELL_SIZE * 0.7),
+                egui::Color32::GRAY,
+            );
+        }
+    }
+}
+
+/// `m>=K o<=J`.
+impl std::fmt::Display for CountRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m>={} o<={}", self.min_mine, self.max_opponent)
+    }
+}
+
+/// Parse the `Display` form, e.g. `m>=2 o<=1`.
+impl std::str::FromStr for CountRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rule: {}", s);
+        let (mine, opponent) = s.split_once(' ').ok_or_else(invalid)?;
+        let count = |text: &str, prefix: &str| {
+            text.trim()
+                .strip_prefix(prefix)
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(invalid)
+        };
+        Ok(CountRule {
+            min_mine: count(mine, "m>=")?,
+            max_opponent: count(opponent, "o<=")?,
+        })
+    }
+}
@@ -1,98 +1,189 @@
-use crate::CELL_SIZE;
+pub mod atom;
+pub mod count;
+pub mod pattern;
+pub mod symmetry;
+
 use crate::grid::cell::CellState;
+use crate::grid::neighbourhood::Neighbourhood;
+use crate::grid::table;
+use crate::rule::atom::Atom;
+use crate::rule::count::CountRule;
+use crate::rule::pattern::PatternRule;
+use crate::rule::symmetry::Symmetry;
+use crate::{
+    CELL_SIZE, COLOR_HIGHLIGHT, RULE_COUNT_PROBA, RULE_SYMMETRIC_PROBA, RULE_WILDCARD_PROBA,
+};
 use eframe::egui;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// What makes a cell change owner. \
+/// Serialized as its index when it has one, see `Rule::index`,
+/// otherwise as its compact displayed form, e.g. `"1*2!0/r"` or `"m>=2 o<=1"`.
+#[derive(PartialEq, Clone, Debug, Deserialize)]
+#[serde(try_from = "RuleRepr")]
+pub enum Rule {
+    Count(CountRule),
+    Pattern(PatternRule),
+}
+
+impl Serialize for Rule {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Some(index) = self.index() {
+            return serializer.serialize_u8(index);
+        }
+        match self {
+            Rule::Pattern(pattern) => serializer.collect_str(pattern),
+            Rule::Count(count) => serializer.collect_str(count),
+        }
+    }
+}
 
-#[derive(PartialEq, Clone, Debug)]
-pub struct Rule {
-    pub top: CellState,
-    pub right: CellState,
-    pub bottom: CellState,
-    pub left: CellState,
+/// Serialized forms of `Rule`: its index, the displayed one, or the structs of older saves.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RuleRepr {
+    Index(u8),
+    Compact(String),
+    Legacy(LegacyRule),
+}
+
+#[derive(Deserialize)]
+enum LegacyRule {
+    Count(CountRule),
+    #[serde(untagged)]
+    Pattern(PatternRule),
+}
+
+impl TryFrom<RuleRepr> for Rule {
+    type Error = String;
+
+    fn try_from(repr: RuleRepr) -> Result<Rule, String> {
+        match repr {
+            RuleRepr::Index(index) => Rule::try_from(index),
+            RuleRepr::Compact(text) => text.parse(),
+            RuleRepr::Legacy(LegacyRule::Count(count)) => Ok(Rule::Count(count)),
+            RuleRepr::Legacy(LegacyRule::Pattern(pattern)) => Ok(Rule::Pattern(pattern)),
+        }
+    }
 }
 
 impl Rule {
-    pub fn new() -> Rule {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    /// Von Neumann rule matching exactly one neighbourhood.
+    pub fn exact(top: CellState, right: CellState, bottom: CellState, left: CellState) -> Rule {
+        Rule::exact_cells(&[top, right, bottom, left])
+    }
+
+    /// Rule matching exactly the neighbours `cells`, in the order of `Neighbourhood::offsets`.
+    pub fn exact_cells(cells: &[CellState]) -> Rule {
+        Rule::Pattern(PatternRule {
+            atoms: cells.iter().map(|c| Atom::Cell(*c)).collect(),
+            symmetry: Symmetry::None,
+        })
+    }
+
+    /// Random rule for `neighbourhood` over the cells of the first `nb_players` players,
+    /// never matching an all-neutral neighbourhood.
+    pub fn new(nb_players: usize, neighbourhood: Neighbourhood, rng: &mut impl Rng) -> Rule {
+        let arity = neighbourhood.arity();
+        if rng.gen_bool(RULE_COUNT_PROBA) {
+            return Rule::Count(CountRule {
+                min_mine: rng.gen_range(arity / 2..=arity) as u8,
+                max_opponent: rng.gen_range(0..=arity / 4) as u8,
+            });
+        }
         loop {
-            let rule = Rule {
-                top: match rng.gen_range(0..3) {
-                    0 => CellState::Neutral,
-                    1 => CellState::Player1,
-                    _ => CellState::Player2,
-                },
-                right: match rng.gen_range(0..3) {
-                    0 => CellState::Neutral,
-                    1 => CellState::Player1,
-                    _ => CellState::Player2,
-                },
-                bottom: match rng.gen_range(0..3) {
-                    0 => CellState::Neutral,
-                    1 => CellState::Player1,
-                    _ => CellState::Player2,
-                },
-                left: match rng.gen_range(0..3) {
-                    0 => CellState::Neutral,
-                    1 => CellState::Player1,
-                    _ => CellState::Player2,
-                },
+            let mut pattern = PatternRule {
+                atoms: (0..arity).map(|_| random_atom(nb_players, rng)).collect(),
+                symmetry: Symmetry::None,
             };
-            if rule.top != CellState::Neutral
-                || rule.right != CellState::Neutral
-                || rule.bottom != CellState::Neutral
-                || rule.left != CellState::Neutral
+            if rng.gen_bool(RULE_SYMMETRIC_PROBA) {
+                pattern.symmetry = Symmetry::SYMMETRIC[rng.gen_range(0..Symmetry::SYMMETRIC.len())];
+                // A pattern left unchanged by its transforms is not worth the premium
+                if pattern.variants().len() == 1 {
+                    pattern.symmetry = Symmetry::None;
+                }
+            }
+            if !pattern
+                .atoms
+                .iter()
+                .all(|a| a.matches(0, CellState::Neutral))
             {
-                return rule;
+                return Rule::Pattern(pattern);
             }
         }
     }
 
-    pub fn new_direction(cell: CellState) -> Rule {
-        let mut rng = rand::thread_rng();
-        let pattern: usize = rng.gen_range(0..4);
-        match pattern {
-            0 => Rule {
-                top: cell,
-                right: CellState::Neutral,
-                bottom: CellState::Neutral,
-                left: CellState::Neutral,
-            },
-            1 => Rule {
-                top: CellState::Neutral,
-                right: cell,
-                bottom: CellState::Neutral,
-                left: CellState::Neutral,
-            },
-            2 => Rule {
-                top: CellState::Neutral,
-                right: CellState::Neutral,
-                bottom: cell,
-                left: CellState::Neutral,
-            },
-            _ => Rule {
-                top: CellState::Neutral,
-                right: CellState::Neutral,
-                bottom: CellState::Neutral,
-                left: cell,
-            },
-        }
-    }
-
-    pub fn next(
-        &self,
-        top: CellState,
-        right: CellState,
-        bottom: CellState,
-        left: CellState,
-    ) -> bool {
-        *self
-            == Rule {
-                top,
-                right,
-                bottom,
-                left,
+    /// Rule matching `cell` on a random side and neutral cells elsewhere.
+    pub fn new_direction(
+        cell: CellState,
+        neighbourhood: Neighbourhood,
+        rng: &mut impl Rng,
+    ) -> Rule {
+        let mut cells = vec![CellState::Neutral; neighbourhood.arity()];
+        let direction = rng.gen_range(0..cells.len());
+        cells[direction] = cell;
+        Rule::exact_cells(&cells)
+    }
+
+    /// Compact index, 1 to 80, of the exact von Neumann rules over neutral, Player 1
+    /// and Player 2 cells: their digits read in base 3. `None` for any other rule.
+    pub fn index(&self) -> Option<u8> {
+        let Rule::Pattern(pattern) = self else {
+            return None;
+        };
+        if pattern.atoms.len() != 4 || !pattern.symmetry.is_none() {
+            return None;
+        }
+        let index = pattern
+            .atoms
+            .iter()
+            .try_fold(0u8, |index, atom| match atom {
+                Atom::Cell(state) if state.code() < 3 => Some(index * 3 + state.code()),
+                _ => None,
+            })?;
+        (index > 0).then_some(index)
+    }
+
+    /// Symmetry of a pattern rule, `None` for count rules.
+    pub fn symmetry(&self) -> Symmetry {
+        match self {
+            Rule::Pattern(pattern) => pattern.symmetry,
+            Rule::Count(_) => Symmetry::None,
+        }
+    }
+
+    /// Whether both rules match the same neighbourhoods,
+    /// e.g. symmetric patterns written from another orientation.
+    pub fn equivalent(&self, other: &Rule) -> bool {
+        match (self, other) {
+            (Rule::Pattern(a), Rule::Pattern(b)) => {
+                let (a, b) = (a.variants(), b.variants());
+                a.len() == b.len() && a.iter().all(|v| b.contains(v))
             }
+            _ => self == other,
+        }
+    }
+
+    /// Number of neighbourhoods matched on a board of `nb_players` players
+    /// using `neighbourhood`. \
+    /// Counted without going through the nb_states^arity neighbourhoods,
+    /// so pricing stays cheap on large neighbourhoods.
+    pub fn coverage(&self, nb_players: usize, neighbourhood: Neighbourhood) -> usize {
+        match self {
+            Rule::Pattern(pattern) if pattern.atoms.len() != neighbourhood.arity() => 0,
+            Rule::Pattern(pattern) => pattern.coverage(nb_players + 1),
+            Rule::Count(count) => count.coverage(nb_players, neighbourhood.arity()),
+        }
+    }
+
+    /// Whether the rule, owned by player `owner`, matches the neighbours `cells`,
+    /// in the order of `Neighbourhood::offsets`.
+    pub fn next(&self, owner: u8, cells: &[CellState]) -> bool {
+        match self {
+            Rule::Pattern(pattern) => pattern.matches(owner, cells),
+            Rule::Count(count) => count.matches(owner, cells),
+        }
     }
 
     pub fn show(&self, ui: &mut ::eframe::egui::Ui) {
@@ -103,57 +194,167 @@ impl Rule {
 
         let grid_size = CELL_SIZE * 3.0;
         let cursor = ui.cursor();
-        let x_offset = cursor.min.x;
-        let y_offset = cursor.min.y;
+        let origin = egui::pos2(cursor.min.x + margin_left, cursor.min.y);
 
         let painter = ui.painter();
+        match self {
+            Rule::Pattern(pattern) => pattern.show(painter, origin),
+            Rule::Count(count) => count.show(painter, origin),
+        }
+
+        let (_, response) =
+            ui.allocate_exact_size(egui::vec2(grid_size, grid_size), egui::Sense::hover());
+        match self {
+            Rule::Pattern(pattern) if !pattern.symmetry.is_none() => {
+                response.on_hover_text(pattern.symmetry.to_string());
+            }
+            Rule::Count(count) => {
+                response.on_hover_text(format!(
+                    "Takes cells with at least {} of your cells and at most {} opponent cells around",
+                    count.min_mine, count.max_opponent
+                ));
+            }
+            _ => {}
+        }
+    }
+}
 
-        let cells = [
-            (1, 0, self.top),
-            (2, 1, self.right),
-            (1, 2, self.bottom),
-            (0, 1, self.left),
-        ];
+/// Exact state, or a wildcard with probability `RULE_WILDCARD_PROBA`.
+fn random_atom(nb_players: usize, rng: &mut impl Rng) -> Atom {
+    let state = CellState::from_code(rng.gen_range(0..=nb_players as u8));
+    if !rng.gen_bool(RULE_WILDCARD_PROBA) {
+        return Atom::Cell(state);
+    }
+    match rng.gen_range(0..5) {
+        0 => Atom::Any,
+        1 => Atom::Occupied,
+        2 => Atom::Mine,
+        3 => Atom::Opponent,
+        _ if state == CellState::Neutral => Atom::Occupied,
+        _ => Atom::Not(state),
+    }
+}
+
+/// Show `rules` in a two-column grid.
+pub fn show_rules(ui: &mut egui::Ui, id_salt: impl std::hash::Hash, rules: &[Rule]) {
+    egui::Grid::new(id_salt)
+        .num_columns(2)
+        .max_col_width(CELL_SIZE * 3.0 + 10.0)
+        .spacing([10.0, 10.0])
+        .show(ui, |ui| {
+            for (i, rule) in rules.iter().enumerate() {
+                ui.vertical_centered(|ui| {
+                    rule.show(ui);
+                });
+                if (i + 1) % 2 == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+}
 
-        for (col, row, cell_state) in cells {
-            let x = x_offset + col as f32 * CELL_SIZE + margin_left;
-            let y = y_offset + row as f32 * CELL_SIZE;
+/// Show `rules` in a two-column grid, each with the cells it captured this round. \
+/// The card of the rule `selected` is framed.
+/// Returns the rule under the pointer and the rule clicked this frame, if any.
+pub fn show_rules_captures(
+    ui: &mut egui::Ui,
+    id_salt: impl std::hash::Hash,
+    rules: &[Rule],
+    captures: &[usize],
+    selected: Option<usize>,
+) -> (Option<usize>, Option<usize>) {
+    let id = egui::Id::new(id_salt);
+    let (mut hovered, mut clicked) = (None, None);
+    egui::Grid::new(id)
+        .num_columns(2)
+        .max_col_width(CELL_SIZE * 3.0 + 10.0)
+        .spacing([10.0, 10.0])
+        .show(ui, |ui| {
+            for (i, rule) in rules.iter().enumerate() {
+                let card = ui
+                    .vertical_centered(|ui| {
+                        rule.show(ui);
+                        let count = captures.get(i).copied().unwrap_or(0);
+                        ui.label(egui::RichText::new(format!("+{}", count)).monospace())
+                            .on_hover_text("Cells captured this round, click to pin");
+                    })
+                    .response;
+                let response = ui.interact(card.rect, id.with(i), egui::Sense::click());
+                if response.contains_pointer() {
+                    hovered = Some(i);
+                }
+                if response.clicked() {
+                    clicked = Some(i);
+                }
+                if selected == Some(i) {
+                    ui.painter().rect_stroke(
+                        card.rect.expand(2.0),
+                        2.0,
+                        egui::Stroke::new(2.0, COLOR_HIGHLIGHT),
+                        egui::StrokeKind::Outside,
+                    );
+                }
+                if (i + 1) % 2 == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+    (hovered, clicked)
+}
 
-            let cell_rect =
-                egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(CELL_SIZE, CELL_SIZE));
+/// Rule from its compact index, see `Rule::index`.
+impl TryFrom<u8> for Rule {
+    type Error = String;
 
-            cell_state.show(&painter, cell_rect);
+    fn try_from(index: u8) -> Result<Rule, String> {
+        if !(1..=80).contains(&index) {
+            return Err(format!("rule index not in 1..=80: {}", index));
         }
+        let mut cells = [CellState::Neutral; 4];
+        table::pattern(index as usize, 3, &mut cells);
+        Ok(Rule::exact_cells(&cells))
+    }
+}
 
-        ui.allocate_space(egui::vec2(grid_size, grid_size));
+/// Parse the `Display` form of a rule, with or without its `Rule ` prefix,
+/// or `#` and its compact index. \
+/// Rules matching an all-neutral neighbourhood are rejected, `Rule::new` never makes them.
+impl std::str::FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let text = text.strip_prefix("Rule ").unwrap_or(text);
+        if let Some(index) = text.strip_prefix('#') {
+            let index: u8 = index
+                .parse()
+                .map_err(|_| format!("invalid rule index: {}", index))?;
+            return Rule::try_from(index);
+        }
+        let (rule, arity) = if text.starts_with("m>=") {
+            (Rule::Count(text.parse()?), Neighbourhood::default().arity())
+        } else {
+            let pattern: PatternRule = text.parse()?;
+            let arity = pattern.atoms.len();
+            (Rule::Pattern(pattern), arity)
+        };
+        if rule.next(1, &vec![CellState::Neutral; arity]) {
+            return Err(format!(
+                "rule matching an all-neutral neighbourhood: {}",
+                text
+            ));
+        }
+        Ok(rule)
     }
 }
 
+/// `Rule ` followed by the pattern or the counts.
 impl std::fmt::Display for Rule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let a = match self.top {
-            CellState::Neutral => 0,
-            CellState::Player1 => 1,
-            CellState::Player2 => 2,
-        };
-        let b = match self.right {
-            CellState::Neutral => 0,
-            CellState::Player1 => 1,
-            CellState::Player2 => 2,
-        };
-        let c = match self.bottom {
-            CellState::Neutral => 0,
-            CellState::Player1 => 1,
-            CellState::Player2 => 2,
-        };
-        let d = match self.left {
-            CellState::Neutral => 0,
-            CellState::Player1 => 1,
-            CellState::Player2 => 2,
-        };
-        let rule_number = a * 1000 + b * 100 + c * 10 + d;
-
-        write!(f, "Rule {}", rule_number)
+        match self {
+            Rule::Pattern(pattern) => write!(f, "Rule {}", pattern),
+            Rule::Count(count) => write!(f, "Rule {}", count),
+        }
     }
 }
 
@@ -162,35 +363,258 @@ mod tests {
     use super::*;
     use crate::grid::cell::CellState;
 
+    fn pattern(rule: Rule) -> PatternRule {
+        match rule {
+            Rule::Pattern(pattern) => pattern,
+            Rule::Count(_) => panic!("not a pattern rule"),
+        }
+    }
+
     #[test]
     fn test_rule_next_true() {
-        let rule = Rule {
-            top: CellState::Player1,
-            right: CellState::Neutral,
-            bottom: CellState::Player2,
-            left: CellState::Player1,
-        };
-        assert!(rule.next(
+        let rule = Rule::exact(
             CellState::Player1,
             CellState::Neutral,
             CellState::Player2,
             CellState::Player1,
+        );
+        assert!(rule.next(
+            1,
+            &[
+                CellState::Player1,
+                CellState::Neutral,
+                CellState::Player2,
+                CellState::Player1
+            ]
         ));
     }
 
     #[test]
     fn test_rule_next_false() {
-        let rule = Rule {
-            top: CellState::Player1,
-            right: CellState::Neutral,
-            bottom: CellState::Player2,
-            left: CellState::Player1,
-        };
-        assert!(!rule.next(
-            CellState::Player2,
+        let rule = Rule::exact(
+            CellState::Player1,
             CellState::Neutral,
             CellState::Player2,
             CellState::Player1,
+        );
+        assert!(!rule.next(
+            1,
+            &[
+                CellState::Player2,
+                CellState::Neutral,
+                CellState::Player2,
+                CellState::Player1
+            ]
         ));
     }
+
+    #[test]
+    fn test_rule_wildcards() {
+        use CellState::{Neutral, Player1, Player2, Player3};
+        let rule = Rule::Pattern(PatternRule {
+            atoms: vec![Atom::Mine, Atom::Opponent, Atom::Any, Atom::Not(Player3)],
+            symmetry: Symmetry::None,
+        });
+        assert!(rule.next(1, &[Player1, Player2, Neutral, Player2]));
+        assert!(rule.next(2, &[Player2, Player3, Player3, Neutral]));
+        assert!(!rule.next(1, &[Player2, Player2, Neutral, Neutral]));
+        assert!(!rule.next(1, &[Player1, Player1, Neutral, Neutral]));
+        assert!(!rule.next(1, &[Player1, Player2, Neutral, Player3]));
+
+        assert_eq!(rule.to_string(), "Rule mo*!3");
+        // 1 * 2 * 4 * 3 neighbourhoods with 3 players
+        assert_eq!(rule.coverage(3, Neighbourhood::VonNeumann), 24);
+        assert_eq!(
+            Rule::exact(Player1, Neutral, Neutral, Neutral).coverage(4, Neighbourhood::VonNeumann),
+            1
+        );
+    }
+
+    #[test]
+    fn test_rule_serde() {
+        let rule = Rule::Pattern(PatternRule {
+            atoms: vec![
+                Atom::Cell(CellState::Player1),
+                Atom::Not(CellState::Neutral),
+                Atom::Any,
+                Atom::Cell(CellState::Neutral),
+            ],
+            symmetry: Symmetry::Mirrors,
+        });
+        let json = serde_json::to_string(&rule).unwrap();
+        assert_eq!(json, r#""1!0*0/m""#);
+        assert_eq!(serde_json::from_str::<Rule>(&json).unwrap(), rule);
+
+        // Rules of older saves
+        let old = r#"{"atoms":["Player1",{"Not":"Neutral"},"Any","Neutral"],"symmetry":"Mirrors"}"#;
+        assert_eq!(serde_json::from_str::<Rule>(old).unwrap(), rule);
+        let old = r#"{"top":"Player1","right":{"Not":"Neutral"},"bottom":"Any","left":"Neutral","symmetry":"Mirrors"}"#;
+        assert_eq!(serde_json::from_str::<Rule>(old).unwrap(), rule);
+
+        let rule = Rule::Count(CountRule {
+            min_mine: 2,
+            max_opponent: 1,
+        });
+        let json = serde_json::to_string(&rule).unwrap();
+        assert_eq!(json, r#""m>=2 o<=1""#);
+        assert_eq!(serde_json::from_str::<Rule>(&json).unwrap(), rule);
+        let old = r#"{"Count":{"min_mine":2,"max_opponent":1}}"#;
+        assert_eq!(serde_json::from_str::<Rule>(old).unwrap(), rule);
+
+        // Exact von Neumann rules between two players are stored as their index
+        let rule: Rule = "1020".parse().unwrap();
+        assert_eq!(serde_json::to_string(&rule).unwrap(), "33");
+        assert_eq!(serde_json::from_str::<Rule>("33").unwrap(), rule);
+        assert_eq!(serde_json::from_str::<Rule>(r#""1020""#).unwrap(), rule);
+        assert!(serde_json::from_str::<Rule>("81").is_err());
+    }
+
+    #[test]
+    fn test_rule_symmetry() {
+        use CellState::{Neutral, Player1, Player2};
+        let symmetric = |top, right, bottom, left, symmetry| {
+            Rule::Pattern(PatternRule {
+                symmetry,
+                ..pattern(Rule::exact(top, right, bottom, left))
+            })
+        };
+        let rule = symmetric(Player1, Player2, Neutral, Neutral, Symmetry::Rotations);
+        assert!(rule.next(1, &[Neutral, Player1, Player2, Neutral]));
+        assert!(rule.next(1, &[Player2, Neutral, Neutral, Player1]));
+        // Mirror image, not a rotation
+        assert!(!rule.next(1, &[Player1, Neutral, Neutral, Player2]));
+        assert_eq!(rule.coverage(2, Neighbourhood::VonNeumann), 4);
+
+        let rule = symmetric(Player1, Player2, Neutral, Neutral, Symmetry::All);
+        assert!(rule.next(1, &[Player1, Neutral, Neutral, Player2]));
+        assert_eq!(rule.coverage(2, Neighbourhood::VonNeumann), 8);
+        assert_eq!(rule.to_string(), "Rule 1200/rm");
+
+        // Same patterns from another base orientation
+        let rotated = symmetric(Neutral, Player1, Player2, Neutral, Symmetry::All);
+        assert!(rule.equivalent(&rotated));
+        assert!(!rule.equivalent(&Rule::exact(Player1, Player2, Neutral, Neutral)));
+    }
+
+    #[test]
+    fn test_rule_symmetry_moore_hex() {
+        use CellState::{Neutral as N, Player1 as A};
+        let symmetric = |cells: &[CellState], symmetry| {
+            Rule::Pattern(PatternRule {
+                symmetry,
+                ..pattern(Rule::exact_cells(cells))
+            })
+        };
+        // Moore: a quarter turn moves the top neighbour to the right one
+        let rule = symmetric(&[A, A, N, N, N, N, N, N], Symmetry::Rotations);
+        assert!(rule.next(1, &[N, N, A, A, N, N, N, N]));
+        assert!(!rule.next(1, &[N, A, A, N, N, N, N, N]));
+        assert_eq!(rule.coverage(1, Neighbourhood::Moore), 4);
+        // Top and top-right mirrored to top and top-left
+        let rule = symmetric(&[A, A, N, N, N, N, N, N], Symmetry::Mirrors);
+        assert!(rule.next(1, &[A, N, N, N, N, N, N, A]));
+        assert!(rule.next(1, &[N, N, N, A, A, N, N, N]));
+        assert!(!rule.next(1, &[N, N, A, A, N, N, N, N]));
+        assert_eq!(rule.coverage(1, Neighbourhood::Moore), 4);
+
+        // Hex: six rotations, mirrors swap top-right and top-left
+        let rule = symmetric(&[A, A, N, N, N, N], Symmetry::All);
+        assert!(rule.next(1, &[N, N, N, N, A, A]));
+        assert!(rule.next(1, &[A, N, N, N, N, A]));
+        assert_eq!(rule.coverage(1, Neighbourhood::Hex), 6);
+        assert_eq!(rule.coverage(1, Neighbourhood::VonNeumann), 0);
+    }
+
+    #[test]
+    fn test_rule_coverage_matches_enumeration() {
+        use rand::SeedableRng;
+        let mut rng = crate::game::GameRng::seed_from_u64(3);
+        for (nb_players, neighbourhood) in [
+            (2, Neighbourhood::VonNeumann),
+            (4, Neighbourhood::VonNeumann),
+            (2, Neighbourhood::Moore),
+            (3, Neighbourhood::Hex),
+        ] {
+            let nb_states = nb_players + 1;
+            let mut cells = vec![CellState::Neutral; neighbourhood.arity()];
+            for _ in 0..20 {
+                let mut rule = Rule::new(nb_players, neighbourhood, &mut rng);
+                for symmetry in [
+                    Symmetry::None,
+                    Symmetry::Rotations,
+                    Symmetry::Mirrors,
+                    Symmetry::All,
+                ] {
+                    if let Rule::Pattern(pattern) = &mut rule {
+                        pattern.symmetry = symmetry;
+                    }
+                    let expected = (0..nb_states.pow(cells.len() as u32))
+                        .filter(|&index| {
+                            table::pattern(index, nb_states, &mut cells);
+                            rule.next(1, &cells)
+                        })
+                        .count();
+                    assert_eq!(
+                        rule.coverage(nb_players, neighbourhood),
+                        expected,
+                        "{}",
+                        rule
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_count() {
+        use CellState::{Neutral, Player1, Player2, Player3};
+        let rule = Rule::Count(CountRule {
+            min_mine: 2,
+            max_opponent: 1,
+        });
+        assert!(rule.next(1, &[Player1, Neutral, Player1, Player2]));
+        assert!(rule.next(1, &[Player1, Player1, Player1, Player1]));
+        assert!(!rule.next(1, &[Player1, Neutral, Neutral, Player2]));
+        assert!(!rule.next(1, &[Player1, Player1, Player2, Player3]));
+        assert!(rule.next(3, &[Player3, Player3, Player2, Neutral]));
+        assert_eq!(rule.to_string(), "Rule m>=2 o<=1");
+        // 2 mine: 6 placements * 3 others (no opponent, either one);
+        // 3 mine: 4 placements * 2; 4 mine: 1
+        assert_eq!(rule.coverage(2, Neighbourhood::VonNeumann), 18 + 8 + 1);
+    }
+
+    #[test]
+    fn test_rule_parse() {
+        use CellState::{Neutral, Player1, Player2};
+        let rule: Rule = "Rule 1020".parse().unwrap();
+        assert_eq!(rule, Rule::exact(Player1, Neutral, Player2, Neutral));
+        assert_eq!(rule.index(), Some(27 + 6));
+        assert_eq!("#33".parse::<Rule>().unwrap(), rule);
+
+        for text in ["mo*!3/rm", "m>=2 o<=1", "12000001", "+00000/r"] {
+            let rule: Rule = text.parse().unwrap();
+            assert_eq!(rule.to_string(), format!("Rule {}", text));
+            assert_eq!(rule.index(), None);
+        }
+
+        for text in [
+            "0000",
+            "**!1*",
+            "102",
+            "1020/x",
+            "1050",
+            "m>=0 o<=1",
+            "#0",
+            "#81",
+        ] {
+            assert!(text.parse::<Rule>().is_err(), "{}", text);
+        }
+
+        for index in 1..=80 {
+            let rule = Rule::try_from(index).unwrap();
+            assert_eq!(rule.index(), Some(index));
+            let text = rule.to_string();
+            assert_eq!(text.parse::<Rule>().unwrap(), rule);
+        }
+    }
 }
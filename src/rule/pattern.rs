@@ -0,0 +1,234 @@
+use crate::grid::cell::CellState;
+use crate::grid::neighbourhood::Neighbourhood;
+use crate::rule::atom::Atom;
+use crate::rule::symmetry::{self, Symmetry};
+use crate::{CELL_SIZE, COLOR_SYMMETRY_BADGE};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Rule matching the neighbours one by one.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "PatternRepr")]
+pub struct PatternRule {
+    /// One atom per neighbour, in the order of `Neighbourhood::offsets`.
+    /// Their number tells the neighbourhood the rule is made for.
+    pub atoms: Vec<Atom>,
+    /// Transforms of the pattern also matched
+    pub symmetry: Symmetry,
+}
+
+/// Serialized forms of `PatternRule`, including the von Neumann one of older saves.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternRepr {
+    Atoms {
+        atoms: Vec<Atom>,
+        #[serde(default)]
+        symmetry: Symmetry,
+    },
+    VonNeumann {
+        top: Atom,
+        right: Atom,
+        bottom: Atom,
+        left: Atom,
+        #[serde(default)]
+        symmetry: Symmetry,
+    },
+}
+
+impl From<PatternRepr> for PatternRule {
+    fn from(repr: PatternRepr) -> PatternRule {
+        match repr {
+            PatternRepr::Atoms { atoms, symmetry } => PatternRule { atoms, symmetry },
+            PatternRepr::VonNeumann {
+                top,
+                right,
+                bottom,
+                left,
+                symmetry,
+            } => PatternRule {
+                atoms: vec![top, right, bottom, left],
+                symmetry,
+            },
+        }
+    }
+}
+
+impl PatternRule {
+    /// Neighbourhood the pattern is made for.
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        Neighbourhood::from_arity(self.atoms.len()).unwrap_or_default()
+    }
+
+    /// Distinct patterns matched: the base one and its transforms by `symmetry`.
+    pub fn variants(&self) -> Vec<Vec<Atom>> {
+        let neighbourhood = self.neighbourhood();
+        let mut variants: Vec<Vec<Atom>> = Vec::new();
+        for t in self.symmetry.transforms(neighbourhood) {
+            let variant = (0..self.atoms.len())
+                .map(|i| self.atoms[symmetry::transform(neighbourhood, t, i)])
+                .collect();
+            if !variants.contains(&variant) {
+                variants.push(variant);
+            }
+        }
+        variants
+    }
+
+    /// States accepted at each position of each variant when owned by `owner`,
+    /// bit `s` standing for the state of code `s` among the first `nb_states`.
+    pub fn accepted(&self, owner: u8, nb_states: usize) -> Vec<Vec<u8>> {
+        self.variants()
+            .iter()
+            .map(|variant| {
+                variant
+                    .iter()
+                    .map(|atom| {
+                        (0..nb_states as u8)
+                            .filter(|s| atom.matches(owner, CellState::from_code(*s)))
+                            .fold(0, |mask, s| mask | 1 << s)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Number of neighbourhoods over `nb_states` states matched, from the states each atom
+    /// of each variant accepts: inclusion-exclusion over the variants,
+    /// rather than trying every neighbourhood.
+    pub fn coverage(&self, nb_states: usize) -> usize {
+        let accepted = self.accepted(1, nb_states);
+        let signed: isize = (1..1usize << accepted.len())
+            .map(|subset| {
+                let mut common = vec![u8::MAX; self.atoms.len()];
+                for (j, variant) in accepted.iter().enumerate() {
+                    if subset & 1 << j != 0 {
+                        for (c, a) in common.iter_mut().zip(variant) {
+                            *c &= a;
+                        }
+                    }
+                }
+                let patterns: isize = common.iter().map(|c| c.count_ones() as isize).product();
+                if subset.count_ones() % 2 == 1 {
+                    patterns
+                } else {
+                    -patterns
+                }
+            })
+            .sum();
+        signed as usize
+    }
+
+    /// Whether the pattern, owned by player `owner`, matches the neighbours `cells`.
+    /// Neighbourhoods of another size never match.
+    pub fn matches(&self, owner: u8, cells: &[CellState]) -> bool {
+        if cells.len() != self.atoms.len() {
+            return false;
+        }
+        let neighbourhood = self.neighbourhood();
+        self.symmetry.transforms(neighbourhood).any(|t| {
+            cells.iter().enumerate().all(|(i, cell)| {
+                self.atoms[symmetry::transform(neighbourhood, t, i)].matches(owner, *cell)
+            })
+        })
+    }
+
+    /// Centers of the neighbours in the 3x3 cells square starting at `origin`,
+    /// the center standing for the cell itself.
+    pub fn neighbour_centers(&self, origin: egui::Pos2) -> Vec<egui::Pos2> {
+        let center = origin + egui::vec2(CELL_SIZE, CELL_SIZE) * 1.5;
+        match self.neighbourhood() {
+            // Pointy-top hexagons around the center one, clockwise from the top-right
+            Neighbourhood::Hex => (0..self.atoms.len())
+                .map(|i| {
+                    let angle = (60.0 * i as f32 - 60.0).to_radians();
+                    center + CELL_SIZE * egui::vec2(angle.cos(), angle.sin())
+                })
+                .collect(),
+            neighbourhood => neighbourhood
+                .offsets(0)
+                .iter()
+                .map(|(dx, dy)| center + egui::vec2(*dx as f32, *dy as f32) * CELL_SIZE)
+                .collect(),
+        }
+    }
+
+    /// Paint the pattern in the 3x3 cells square starting at `origin`,
+    /// the center standing for the cell itself.
+    pub fn show(&self, painter: &egui::Painter, origin: egui::Pos2) {
+        let centers = self.neighbour_centers(origin);
+        for (atom, center) in self.atoms.iter().zip(centers) {
+            if self.neighbourhood() == Neighbourhood::Hex {
+                atom.show_hex(painter, center, CELL_SIZE / 3f32.sqrt());
+            } else {
+                let size = egui::vec2(CELL_SIZE, CELL_SIZE);
+                atom.show(painter, egui::Rect::from_center_size(center, size));
+            }
+        }
+
+        let center = origin + egui::vec2(CELL_SIZE, CELL_SIZE) * 1.5;
+        if !self.symmetry.is_none() {
+            // Badge on the cell itself, in the middle of the pattern
+            painter.circle_filled(center, CELL_SIZE / 2.0, COLOR_SYMMETRY_BADGE);
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                self.symmetry.badge(),
+                egui::FontId::proportional(CELL_SIZE * 0.55),
+                egui::Color32::BLACK,
+            );
+        }
+    }
+}
+
+/// Atoms in neighbour order: cell codes for exact states, see `Atom` for wildcards,
+/// followed by the `Symmetry` suffix.
+impl std::fmt::Display for PatternRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for atom in &self.atoms {
+            write!(f, "{}", atom)?;
+        }
+        write!(f, "{}", self.symmetry.suffix())
+    }
+}
+
+/// Parse the `Display` form, e.g. `1020` or `mo*!3/rm`.
+impl std::str::FromStr for PatternRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rule: {}", s);
+        let (text, symmetry) = match s.find('/') {
+            Some(i) => {
+                let suffix = &s[i..];
+                let symmetry = Symmetry::SYMMETRIC
+                    .into_iter()
+                    .find(|symmetry| symmetry.suffix() == suffix)
+                    .ok_or_else(invalid)?;
+                (&s[..i], symmetry)
+            }
+            None => (s, Symmetry::None),
+        };
+
+        let state = |c: Option<char>| match c.and_then(|c| c.to_digit(10)) {
+            Some(code) if code <= 4 => Ok(CellState::from_code(code as u8)),
+            _ => Err(invalid()),
+        };
+        let mut atoms = Vec::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            atoms.push(match c {
+                '*' => Atom::Any,
+                '+' => Atom::Occupied,
+                'm' => Atom::Mine,
+                'o' => Atom::Opponent,
+                '!' => Atom::Not(state(chars.next())?),
+                _ => Atom::Cell(state(Some(c))?),
+            });
+        }
+        if Neighbourhood::from_arity(atoms.len()).is_none() {
+            return Err(format!("a rule has 4, 6 or 8 neighbours: {}", s));
+        }
+        Ok(PatternRule { atoms, symmetry })
+    }
+}
@@ -0,0 +1,79 @@
+use crate::grid::neighbourhood::Neighbourhood;
+use serde::{Deserialize, Serialize};
+
+/// Transforms of its base pattern a rule also matches.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Symmetry {
+    /// Only the base pattern
+    #[default]
+    None,
+    /// Every rotation of the pattern
+    Rotations,
+    /// The pattern mirrored left/right and top/bottom
+    Mirrors,
+    /// Every rotation and reflection
+    All,
+}
+
+impl Symmetry {
+    pub const SYMMETRIC: [Symmetry; 3] = [Symmetry::Rotations, Symmetry::Mirrors, Symmetry::All];
+
+    pub fn is_none(self) -> bool {
+        self == Symmetry::None
+    }
+
+    /// Transforms of a pattern over `neighbourhood`, identity first,
+    /// as (mirrored left/right first, rotation in neighbours). \
+    /// Neighbour `i` of the transformed pattern is neighbour `transform(t, i)` of the base one.
+    pub fn transforms(self, neighbourhood: Neighbourhood) -> impl Iterator<Item = (bool, usize)> {
+        let step = neighbourhood.rotation_step();
+        let rotations = neighbourhood.arity() / step;
+        let (nb_turns, turn, nb_mirrors) = match self {
+            Symmetry::None => (1, 0, 1),
+            Symmetry::Rotations => (rotations, step, 1),
+            // Left/right mirror, and top/bottom as the left/right one and a half turn
+            Symmetry::Mirrors => (2, neighbourhood.arity() / 2, 2),
+            Symmetry::All => (rotations, step, 2),
+        };
+        (0..nb_turns).flat_map(move |t| (0..nb_mirrors).map(move |m| (m == 1, t * turn)))
+    }
+
+    /// Suffix of the rule number, empty without symmetry.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Symmetry::None => "",
+            Symmetry::Rotations => "/r",
+            Symmetry::Mirrors => "/m",
+            Symmetry::All => "/rm",
+        }
+    }
+
+    /// Short text of the badge drawn on symmetric rules.
+    pub fn badge(self) -> &'static str {
+        match self {
+            Symmetry::None => "",
+            Symmetry::Rotations => "R",
+            Symmetry::Mirrors => "M",
+            Symmetry::All => "RM",
+        }
+    }
+}
+
+/// Neighbour of the base pattern seen at neighbour `i` through `transform`, see `Symmetry::transforms`.
+#[inline]
+pub fn transform(neighbourhood: Neighbourhood, (mirror, turn): (bool, usize), i: usize) -> usize {
+    let arity = neighbourhood.arity();
+    let i = (i + arity - turn) % arity;
+    if mirror { neighbourhood.mirror(i) } else { i }
+}
+
+impl std::fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Symmetry::None => write!(f, "No symmetry"),
+            Symmetry::Rotations => write!(f, "Matches every rotation"),
+            Symmetry::Mirrors => write!(f, "Matches its mirror images"),
+            Symmetry::All => write!(f, "Matches every rotation and mirror image"),
+        }
+    }
+}
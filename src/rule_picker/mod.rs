@@ -1,29 +1,38 @@
+use crate::config::GameConfig;
 pub use crate::rule::Rule;
-use crate::{CELL_SIZE, COLOR_PLAYER1, COLOR_PLAYER2, Player, RULE_PICKER_NB_RULES, components};
+use crate::{CELL_SIZE, CellState, Player, components};
 use eframe::egui::{self, vec2};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RulePicker {
-    pub player1_choosing: bool,
+    /// Player (1 to 4) whose turn it is to pick
+    pub current_player: u8,
     pub rules: Vec<Rule>,
     pub rules_available: Vec<bool>, // vrai si on peut sélectionner la règle
 }
 
 impl RulePicker {
-    pub fn new_with_players(player1: &Player, player2: &Player) -> RulePicker {
+    /// Offer `rule_picker_nb_rules` rules owned by none of `players`,
+    /// starting with a random player.
+    pub fn new_with_players(
+        players: &[Player],
+        config: &GameConfig,
+        rng: &mut impl Rng,
+    ) -> RulePicker {
         let mut rules = Vec::new();
-        while rules.len() < RULE_PICKER_NB_RULES {
-            let new_rule = Rule::new();
-            if !rules.contains(&new_rule)
-                && !player1.rules.contains(&new_rule)
-                && !player2.rules.contains(&new_rule)
-            {
+        while rules.len() < config.rule_picker_nb_rules {
+            let new_rule = Rule::new(players.len(), config.neighbourhood, rng);
+            let known = rules.iter().chain(players.iter().flat_map(|p| &p.rules));
+            if !known.into_iter().any(|r| r.equivalent(&new_rule)) {
                 rules.push(new_rule);
             }
         }
         RulePicker {
-            player1_choosing: rand::random(),
+            current_player: rng.gen_range(1..=players.len() as u8),
             rules,
-            rules_available: vec![true; RULE_PICKER_NB_RULES],
+            rules_available: vec![true; config.rule_picker_nb_rules],
         }
     }
 
@@ -33,18 +42,15 @@ impl RulePicker {
         player.rules.push(self.rules[index].clone());
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui, player: &mut Player) {
+    /// Returns the index of the rule selected this frame, if any.
+    pub fn show(&self, ui: &mut egui::Ui) -> Option<usize> {
+        let mut selected = None;
         components::text::title(ui);
 
         ui.vertical_centered(|ui| {
-            let player_num = if self.player1_choosing { 1 } else { 2 };
             ui.label(
-                egui::RichText::new(format!("Time for player {} to select", player_num))
-                    .color(if self.player1_choosing {
-                        COLOR_PLAYER1
-                    } else {
-                        COLOR_PLAYER2
-                    })
+                egui::RichText::new(format!("Time for player {} to select", self.current_player))
+                    .color(CellState::player(self.current_player).color())
                     .size(18.0)
                     .strong(),
             );
@@ -65,15 +71,14 @@ impl RulePicker {
             egui::Grid::new("rulepicker_grid")
                 .spacing(vec2(SPACING, 20.0))
                 .show(ui, |ui| {
-                    for (i, rule) in self.rules.clone().iter().enumerate() {
+                    for (i, rule) in self.rules.iter().enumerate() {
                         ui.vertical_centered(|ui| {
                             ui.set_min_width(ITEM_WIDTH);
                             rule.show(ui);
 
                             if self.rules_available[i] {
                                 if ui.button("Select").clicked() {
-                                    self.add_rule(player, i);
-                                    self.player1_choosing = !self.player1_choosing;
+                                    selected = Some(i);
                                 };
                             } else {
                                 ui.label("Chosen");
@@ -85,5 +90,7 @@ impl RulePicker {
                     }
                 });
         });
+
+        selected
     }
 }
@@ -0,0 +1,523 @@
+use crate::components;
+use crate::config::{ConflictResolution, GameConfig};
+use crate::constants::MAX_PLAYERS;
+use crate::game::GameRng;
+use crate::grid::Grid;
+use crate::grid::cell::CellState;
+use crate::grid::neighbourhood::Neighbourhood;
+use crate::grid::table::{self, OutcomeTable};
+use crate::rule::Rule;
+use crate::rule::atom::Atom;
+use crate::rule::pattern::PatternRule;
+use crate::rule::symmetry::Symmetry;
+use crate::{CELL_SIZE, GRID_SIZE};
+use eframe::egui;
+use rand::SeedableRng;
+
+/// Free play outside a match: hand-made rule sets on a hand-painted grid,
+/// run, stepped and reset at will.
+pub struct Sandbox {
+    /// Players, neighbourhood, conflict resolution and spawn probability in use
+    pub config: GameConfig,
+    /// Rules of each player, in player order
+    pub rules: Vec<Vec<Rule>>,
+    /// Grid the simulation starts from, as painted
+    pub initial_grid: Grid,
+    pub grid: Grid,
+    pub iteration: usize,
+    pub playing: bool,
+    /// Pattern being edited by clicking its neighbours
+    pub pattern: PatternRule,
+    /// Rule typed instead of edited
+    pub rule_text: String,
+    /// State painted on the grid
+    pub brush: CellState,
+    seed: u64,
+    rng: GameRng,
+    table: Option<OutcomeTable>,
+}
+
+impl Sandbox {
+    /// Empty grid and rule sets with the players, neighbourhood and conflict resolution of `config`.
+    pub fn new(config: GameConfig) -> Sandbox {
+        let seed = rand::random();
+        let grid = Grid::new(config.grid_size, &[], &mut GameRng::seed_from_u64(seed))
+            .with_neighbourhood(config.neighbourhood);
+        Sandbox {
+            rules: vec![Vec::new(); config.nb_players()],
+            initial_grid: grid.clone(),
+            grid,
+            iteration: 0,
+            playing: false,
+            pattern: blank_pattern(config.neighbourhood),
+            rule_text: String::new(),
+            brush: CellState::Player1,
+            seed,
+            rng: GameRng::seed_from_u64(seed),
+            table: None,
+            config,
+        }
+    }
+
+    /// Advance the simulation by one iteration, pausing once the grid is stable.
+    pub fn step(&mut self) {
+        let rule_sets: Vec<&[Rule]> = self.rules.iter().map(Vec::as_slice).collect();
+        let neighbourhood = self.grid.neighbourhood;
+        let conflict = self.config.conflict;
+        let table = match &mut self.table {
+            Some(table) if table.is_for(&rule_sets, neighbourhood, conflict) => table,
+            cached => cached.insert(OutcomeTable::with_conflict(
+                &rule_sets,
+                neighbourhood,
+                conflict,
+            )),
+        };
+        table.set_step(
+            self.iteration,
+            table::step_salt(self.seed, 0, self.iteration),
+        );
+        if self.grid.next_with_table(table, self.config.threads) {
+            self.iteration += 1;
+        } else {
+            self.playing = false;
+        }
+    }
+
+    /// Back to the painted grid.
+    pub fn reset(&mut self) {
+        self.grid = self.initial_grid.clone();
+        self.iteration = 0;
+        self.playing = false;
+    }
+
+    /// Start over from the grid `grid`.
+    fn set_initial_grid(&mut self, grid: Grid) {
+        self.initial_grid = grid;
+        self.reset();
+    }
+
+    /// Paint the cell at `index` with the brush, the current grid becoming the starting one.
+    pub fn paint(&mut self, index: usize) {
+        if self.grid.values.get(index) != self.brush || self.iteration > 0 {
+            self.grid.values.set(index, self.brush);
+            self.set_initial_grid(self.grid.clone());
+        }
+    }
+
+    /// Random grid, spawning every player with `player_spawn_proba`.
+    pub fn randomize(&mut self) {
+        let spawn_probas = vec![self.config.player_spawn_proba; self.rules.len()];
+        let grid = Grid::new(self.config.grid_size, &spawn_probas, &mut self.rng)
+            .with_neighbourhood(self.config.neighbourhood);
+        self.set_initial_grid(grid);
+    }
+
+    pub fn clear(&mut self) {
+        let grid = Grid::new(self.config.grid_size, &[], &mut self.rng)
+            .with_neighbourhood(self.config.neighbourhood);
+        self.set_initial_grid(grid);
+    }
+
+    /// Apply a change of players, neighbourhood or grid size made to `config`,
+    /// keeping the rules and as much of the grid as still fits.
+    fn apply_config(&mut self) {
+        let nb_players = self.config.nb_players();
+        self.rules.resize(nb_players, Vec::new());
+        if self.brush.owner().is_some_and(|p| p as usize > nb_players) {
+            self.brush = CellState::Player1;
+        }
+        if self.pattern.neighbourhood() != self.config.neighbourhood {
+            self.pattern = blank_pattern(self.config.neighbourhood);
+        }
+        if self.initial_grid.width != self.config.grid_size {
+            self.clear();
+            return;
+        }
+
+        let mut grid = self.initial_grid.clone();
+        grid.neighbourhood = self.config.neighbourhood;
+        // Cells of the players removed would not match any pattern of the outcome table
+        for index in 0..grid.values.len() {
+            if grid
+                .values
+                .get(index)
+                .owner()
+                .is_some_and(|p| p as usize > nb_players)
+            {
+                grid.values.set(index, CellState::Neutral);
+            }
+        }
+        self.set_initial_grid(grid);
+    }
+
+    /// Returns `true` when the sandbox should be closed.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut close_clicked = false;
+        components::text::title(ui);
+
+        ui.horizontal(|ui| {
+            components::text::heading(ui, "Sandbox");
+            if ui.button("Back").clicked() {
+                close_clicked = true;
+            }
+        });
+
+        let available_rect = ui.available_rect_before_wrap();
+        if available_rect.width().is_nan() || available_rect.width() <= 0.0 {
+            return close_clicked;
+        }
+
+        let total_width = available_rect.width();
+        let side_width = total_width / 4.0;
+        let grid_section_width = total_width / 2.0;
+
+        let bar_height = 50.0;
+        let mut view_rect = available_rect;
+        view_rect.max.y -= bar_height;
+        let bar_rect = egui::Rect::from_min_max(
+            egui::pos2(available_rect.min.x, view_rect.max.y),
+            available_rect.max,
+        );
+
+        ui.scope_builder(egui::UiBuilder::new().max_rect(view_rect), |ui| {
+            ui.horizontal(|ui| {
+                ui.scope(|ui| {
+                    ui.set_min_width(side_width);
+                    ui.set_max_width(side_width);
+                    egui::ScrollArea::vertical()
+                        .id_salt("sandbox_editor")
+                        .show(ui, |ui| self.show_editor(ui));
+                });
+
+                ui.scope(|ui| {
+                    ui.set_min_width(grid_section_width);
+                    ui.set_max_width(grid_section_width);
+                    ui.vertical_centered(|ui| {
+                        self.show_controls(ui);
+                        ui.add_space(5.0);
+                        if let Some(index) = self.grid.show_paintable(ui) {
+                            self.paint(index);
+                        }
+                    });
+                });
+
+                ui.scope(|ui| {
+                    ui.set_min_width(side_width);
+                    ui.set_max_width(side_width);
+                    egui::ScrollArea::vertical()
+                        .id_salt("sandbox_rules")
+                        .show(ui, |ui| self.show_rule_sets(ui));
+                });
+            });
+        });
+
+        ui.scope_builder(egui::UiBuilder::new().max_rect(bar_rect), |ui| {
+            components::score_bar::show(ui, grid_section_width, &self.grid);
+        });
+
+        close_clicked
+    }
+
+    /// Settings, then the pattern editor and the typed rule entry.
+    fn show_editor(&mut self, ui: &mut egui::Ui) {
+        components::text::heading_small(ui, "Settings");
+        let before = (
+            self.config.players,
+            self.config.neighbourhood,
+            self.config.grid_size,
+        );
+        egui::Grid::new("sandbox_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Players");
+                ui.add(egui::Slider::new(&mut self.config.players, 2..=MAX_PLAYERS));
+                ui.end_row();
+
+                ui.label("Grid size");
+                ui.add(egui::Slider::new(
+                    &mut self.config.grid_size,
+                    8..=GRID_SIZE * 2,
+                ));
+                ui.end_row();
+
+                ui.label("Neighbourhood");
+                egui::ComboBox::from_id_salt("sandbox_neighbourhood")
+                    .selected_text(self.config.neighbourhood.to_string())
+                    .show_ui(ui, |ui| {
+                        for neighbourhood in Neighbourhood::ALL {
+                            ui.selectable_value(
+                                &mut self.config.neighbourhood,
+                                neighbourhood,
+                                neighbourhood.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Conflicts");
+                egui::ComboBox::from_id_salt("sandbox_conflict")
+                    .selected_text(self.config.conflict.to_string())
+                    .show_ui(ui, |ui| {
+                        for conflict in ConflictResolution::ALL {
+                            ui.selectable_value(
+                                &mut self.config.conflict,
+                                conflict,
+                                conflict.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+            });
+        let after = (
+            self.config.players,
+            self.config.neighbourhood,
+            self.config.grid_size,
+        );
+        if after != before {
+            self.apply_config();
+        }
+
+        ui.add_space(10.0);
+        components::text::heading_small(ui, "Rule editor");
+        ui.label("Click a neighbour to cycle through the states and wildcards.");
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(CELL_SIZE * 3.0, CELL_SIZE * 3.0),
+            egui::Sense::click(),
+        );
+        self.pattern.show(ui.painter(), rect.min);
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        {
+            let clicked = self
+                .pattern
+                .neighbour_centers(rect.min)
+                .iter()
+                .position(|center| center.distance(pos) < CELL_SIZE / 2.0);
+            if let Some(i) = clicked {
+                self.pattern.atoms[i] = next_atom(self.pattern.atoms[i], self.rules.len());
+            }
+        }
+
+        let symmetry = &mut self.pattern.symmetry;
+        egui::ComboBox::from_id_salt("sandbox_symmetry")
+            .selected_text(symmetry.to_string())
+            .show_ui(ui, |ui| {
+                ui.selectable_value(symmetry, Symmetry::None, Symmetry::None.to_string());
+                for s in Symmetry::SYMMETRIC {
+                    ui.selectable_value(symmetry, s, s.to_string());
+                }
+            });
+        let rule = Rule::Pattern(self.pattern.clone());
+        ui.label(rule.to_string());
+        self.show_add_buttons(ui, rule, "edited");
+
+        ui.add_space(10.0);
+        components::text::heading_small(ui, "Type a rule");
+        if let Some(rule) = components::rule_entry::show(ui, &mut self.rule_text) {
+            self.show_add_buttons(ui, rule, "typed");
+        }
+    }
+
+    /// Why `rule` cannot be added with the current settings, if it cannot: a pattern of
+    /// another neighbourhood, a player not in the sandbox, or a match on all-neutral neighbours.
+    pub fn rule_problem(&self, rule: &Rule) -> Option<String> {
+        let neighbourhood = self.config.neighbourhood;
+        if let Rule::Pattern(pattern) = rule {
+            if pattern.atoms.len() != neighbourhood.arity() {
+                return Some(format!(
+                    "Written for the {} neighbourhood, not {}.",
+                    pattern.neighbourhood(),
+                    neighbourhood
+                ));
+            }
+            let player = pattern
+                .atoms
+                .iter()
+                .filter_map(|atom| match atom {
+                    Atom::Cell(state) | Atom::Not(state) => state.owner(),
+                    _ => None,
+                })
+                .find(|player| *player as usize > self.rules.len());
+            if let Some(player) = player {
+                return Some(format!(
+                    "Refers to Player {}, but there are {} players.",
+                    player,
+                    self.rules.len()
+                ));
+            }
+        }
+        if rule.next(1, &vec![CellState::Neutral; neighbourhood.arity()]) {
+            return Some("Matches an all-neutral neighbourhood, pick at least one state.".into());
+        }
+        None
+    }
+
+    /// One button per player giving them `rule`, or why it cannot be added.
+    fn show_add_buttons(&mut self, ui: &mut egui::Ui, rule: Rule, id_salt: &str) {
+        if let Some(problem) = self.rule_problem(&rule) {
+            ui.label(problem);
+            return;
+        }
+        ui.push_id(id_salt, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (i, rules) in self.rules.iter_mut().enumerate() {
+                    let color = CellState::player(i as u8 + 1).color();
+                    let label = egui::RichText::new(format!("Add to P{}", i + 1)).color(color);
+                    if ui.button(label).clicked() && !rules.contains(&rule) {
+                        rules.push(rule.clone());
+                    }
+                }
+            });
+        });
+    }
+
+    /// Run, step and reset buttons, then the brush.
+    fn show_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let play_label = if self.playing { "Pause" } else { "Run" };
+            if ui.button(play_label).clicked() {
+                self.playing = !self.playing;
+            }
+            if ui.button("Step").clicked() {
+                self.playing = false;
+                self.step();
+            }
+            if ui.button("Reset").clicked() {
+                self.reset();
+            }
+            ui.separator();
+            if ui.button("Random grid").clicked() {
+                self.randomize();
+            }
+            if ui.button("Clear grid").clicked() {
+                self.clear();
+            }
+            ui.separator();
+            ui.label(format!("Iteration {}", self.iteration));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Brush");
+            let states = std::iter::once(CellState::Neutral)
+                .chain((1..=self.rules.len() as u8).map(CellState::player));
+            for state in states {
+                let name = match state.owner() {
+                    Some(player) => format!("Player {}", player),
+                    None => "Neutral".to_string(),
+                };
+                let label = egui::RichText::new(name).color(state.color()).strong();
+                ui.selectable_value(&mut self.brush, state, label);
+            }
+        });
+    }
+
+    /// Rules of every player, each with a remove button.
+    fn show_rule_sets(&mut self, ui: &mut egui::Ui) {
+        for (i, rules) in self.rules.iter_mut().enumerate() {
+            ui.heading(
+                egui::RichText::new(format!("Player {}", i + 1))
+                    .color(CellState::player(i as u8 + 1).color())
+                    .size(18.0)
+                    .strong(),
+            );
+            ui.add_space(5.0);
+            if rules.is_empty() {
+                ui.label("No rules");
+            }
+            let mut removed = None;
+            egui::Grid::new(("sandbox_rules", i))
+                .num_columns(2)
+                .spacing([10.0, 10.0])
+                .show(ui, |ui| {
+                    for (j, rule) in rules.iter().enumerate() {
+                        ui.vertical_centered(|ui| rule.show(ui));
+                        if ui.button("Remove").clicked() {
+                            removed = Some(j);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(j) = removed {
+                rules.remove(j);
+            }
+            ui.add_space(15.0);
+        }
+    }
+}
+
+/// Pattern of neutral neighbours over `neighbourhood`.
+fn blank_pattern(neighbourhood: Neighbourhood) -> PatternRule {
+    PatternRule {
+        atoms: vec![Atom::Cell(CellState::Neutral); neighbourhood.arity()],
+        symmetry: Symmetry::None,
+    }
+}
+
+/// Atom after `atom` when cycling through the states of `nb_players` players, then the wildcards.
+fn next_atom(atom: Atom, nb_players: usize) -> Atom {
+    match atom {
+        Atom::Cell(state) if (state.code() as usize) < nb_players => {
+            Atom::Cell(CellState::from_code(state.code() + 1))
+        }
+        Atom::Cell(_) => Atom::Any,
+        Atom::Any => Atom::Occupied,
+        Atom::Occupied => Atom::Mine,
+        Atom::Mine => Atom::Opponent,
+        Atom::Opponent | Atom::Not(_) => Atom::Cell(CellState::Neutral),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paint_step_reset() {
+        let config = GameConfig {
+            grid_size: 8,
+            ..GameConfig::default()
+        };
+        let mut sandbox = Sandbox::new(config);
+        // Player 1 takes the cell below each of its cells
+        sandbox.rules[0].push("1000".parse().unwrap());
+        sandbox.paint(0);
+        assert_eq!(sandbox.initial_grid.count(CellState::Player1), 1);
+
+        sandbox.step();
+        sandbox.step();
+        assert_eq!(sandbox.iteration, 2);
+        assert_eq!(sandbox.grid.count(CellState::Player1), 3);
+
+        sandbox.reset();
+        assert_eq!(sandbox.grid.values, sandbox.initial_grid.values);
+
+        // Fewer players: the cells of the players removed turn neutral
+        sandbox.config.players = 3;
+        sandbox.apply_config();
+        sandbox.brush = CellState::Player3;
+        sandbox.paint(5);
+        sandbox.config.players = 2;
+        sandbox.apply_config();
+        assert_eq!(sandbox.rules.len(), 2);
+        assert_eq!(sandbox.grid.count(CellState::Player3), 0);
+        assert_eq!(sandbox.brush, CellState::Player1);
+    }
+
+    #[test]
+    fn test_rule_problems() {
+        let mut sandbox = Sandbox::new(GameConfig::default());
+        let problem = |sandbox: &Sandbox, rule: &str| sandbox.rule_problem(&rule.parse().unwrap());
+        assert_eq!(problem(&sandbox, "1000"), None);
+        assert_eq!(problem(&sandbox, "m>=2 o<=1"), None);
+        let blank = Rule::Pattern(blank_pattern(Neighbourhood::VonNeumann));
+        assert!(sandbox.rule_problem(&blank).is_some());
+        // A Moore pattern in a von Neumann sandbox
+        assert!(problem(&sandbox, "10000000").is_some());
+        // Player 3 in a 2-player sandbox
+        assert!(problem(&sandbox, "1030").is_some());
+        assert!(problem(&sandbox, "1!300").is_some());
+        sandbox.config.players = 3;
+        sandbox.apply_config();
+        assert_eq!(problem(&sandbox, "1030"), None);
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// Error raised while writing or reading a save file.
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "save file error: {}", e),
+            SaveError::Format(e) => write!(f, "invalid save file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Format(e)
+    }
+}
+
+/// Write `value` as JSON to `path`. \
+/// The file is written next to `path` first then renamed, so an interrupted
+/// save never corrupts the previous one.
+pub fn save_to_file<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), SaveError> {
+    let path = path.as_ref();
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec(value)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn load_from_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, SaveError> {
+    let data = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::engine::{GameEngine, Phase};
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let mut engine = GameEngine::new_with_seed(GameConfig::default(), 7);
+        let mut index = 0;
+        while engine.phase == Phase::RulePicking {
+            engine.pick_rule(index);
+            index += 1;
+        }
+        engine.step();
+
+        let path = std::env::temp_dir().join("nesw4_test_save_roundtrip.json");
+        save_to_file(&engine, &path).unwrap();
+        let mut loaded: GameEngine = load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.phase, engine.phase);
+        assert_eq!(loaded.game.iteration, engine.game.iteration);
+        assert_eq!(loaded.game.grid.values, engine.game.grid.values);
+        assert_eq!(loaded.game.players[0].rules, engine.game.players[0].rules);
+        assert_eq!(loaded.game.players[1].money, engine.game.players[1].money);
+
+        // The RNG state is saved too, so the match continues identically
+        engine.run_round();
+        loaded.run_round();
+        engine.open_shop();
+        loaded.open_shop();
+        assert_eq!(loaded.game.grid.values, engine.game.grid.values);
+        assert_eq!(loaded.game.shop.rules, engine.game.shop.rules);
+    }
+}
@@ -1,111 +1,196 @@
-use crate::constants::{
-    COLOR_PLAYER1, COLOR_PLAYER2, PLAYER_MAX_RULES, SHOP_ADD_SPAWN_PROBA, SHOP_NB_RULES,
-    SHOP_PRICE_DELETE_RULE, SHOP_PRICE_RULE, SHOP_PRICE_SPAWN,
-};
-use crate::player::Player;
+use crate::config::GameConfig;
+use crate::grid::cell::CellState;
+use crate::player::{Player, two_mut};
 use crate::rule::Rule;
 use crate::{CELL_SIZE, components};
 use eframe::egui;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Reason a shop operation was refused.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ShopError {
+    NotEnoughMoney,
+    TooManyRules,
+    AlreadyBought,
+    InvalidIndex,
+    SpawnMaxed,
+}
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Shop {
     pub rules: Vec<Rule>,
     pub bought_rules: Vec<bool>,
-    pub current_player: u8,
-    pub player1_shopped: bool,
-    pub player2_shopped: bool,
+    /// Players in shopping order
+    pub order: Vec<u8>,
+    /// Index in `order` of the player shopping, `order.len()` once everyone is done
+    pub turn: usize,
+    pub config: GameConfig,
+    /// Price of each rule, computed when the shop is stocked
+    #[serde(default)]
+    pub prices: Vec<usize>,
+}
+
+/// `shop_price_rule` per neighbourhood a pattern covers, plus `shop_price_symmetry`
+/// for symmetric patterns, or `shop_price_count_rule`.
+fn rule_price(config: &GameConfig, rule: &Rule) -> usize {
+    match rule {
+        Rule::Count(_) => config.shop_price_count_rule,
+        Rule::Pattern(pattern) => {
+            let premium = if pattern.symmetry.is_none() {
+                0
+            } else {
+                config.shop_price_symmetry
+            };
+            rule.coverage(config.nb_players(), config.neighbourhood) * config.shop_price_rule
+                + premium
+        }
+    }
 }
 
 impl Shop {
-    pub fn new_with_players(player1: &Player, player2: &Player) -> Shop {
+    /// Stock `shop_nb_rules` rules owned by none of `players`.
+    pub fn new_with_players(players: &[Player], config: &GameConfig, rng: &mut impl Rng) -> Shop {
         let mut rules = Vec::new();
-        while rules.len() < SHOP_NB_RULES {
-            let new_rule = Rule::new();
-            if !rules.contains(&new_rule)
-                && !player1.rules.contains(&new_rule)
-                && !player2.rules.contains(&new_rule)
-            {
+        while rules.len() < config.shop_nb_rules {
+            let new_rule = Rule::new(players.len(), config.neighbourhood, rng);
+            let known = rules.iter().chain(players.iter().flat_map(|p| &p.rules));
+            if !known.into_iter().any(|r| r.equivalent(&new_rule)) {
                 rules.push(new_rule);
             }
         }
         Shop {
+            prices: rules.iter().map(|rule| rule_price(config, rule)).collect(),
             rules,
-            current_player: 1,
-            bought_rules: vec![false; SHOP_NB_RULES],
-            player1_shopped: false,
-            player2_shopped: false,
+            bought_rules: vec![false; config.shop_nb_rules],
+            order: (1..=players.len() as u8).collect(),
+            turn: 0,
+            config: config.clone(),
         }
     }
 
-    pub fn buy_rule(&mut self, player: &mut Player, index: usize) -> Result<(), ()> {
-        if player.money < SHOP_PRICE_RULE {
-            return Err(());
-        }
-        if player.rules.len() >= PLAYER_MAX_RULES {
-            return Err(());
+    /// Player whose turn it is, `None` once everyone has shopped.
+    pub fn current_player(&self) -> Option<u8> {
+        self.order.get(self.turn).copied()
+    }
+
+    /// Price of the rule at `index`, see `rule_price`.
+    pub fn price(&self, index: usize) -> usize {
+        match self.prices.get(index) {
+            Some(price) => *price,
+            // Shops saved before prices were stored
+            None => self
+                .rules
+                .get(index)
+                .map_or(0, |rule| rule_price(&self.config, rule)),
         }
+    }
+
+    pub fn buy_rule(&mut self, player: &mut Player, index: usize) -> Result<(), ShopError> {
         if index >= self.rules.len() {
-            return Err(());
+            return Err(ShopError::InvalidIndex);
+        }
+        let price = self.price(index);
+        if player.money < price {
+            return Err(ShopError::NotEnoughMoney);
+        }
+        if player.rules.len() >= player.max_rules {
+            return Err(ShopError::TooManyRules);
+        }
+        if self.bought_rules[index] {
+            return Err(ShopError::AlreadyBought);
         }
         player.rules.push(self.rules[index].clone());
-        player.money -= SHOP_PRICE_RULE;
+        player.money -= price;
+        player.money_spent += price;
+        self.bought_rules[index] = true;
         Ok(())
     }
 
-    pub fn buy_spawn(&mut self, player: &mut Player) -> Result<(), ()> {
-        if player.money < SHOP_PRICE_SPAWN {
-            return Err(());
+    pub fn buy_spawn(&mut self, player: &mut Player) -> Result<(), ShopError> {
+        if player.money < self.config.shop_price_spawn {
+            return Err(ShopError::NotEnoughMoney);
         }
         if player.spawn_proba >= 50.0 {
-            return Err(());
+            return Err(ShopError::SpawnMaxed);
         }
-        player.spawn_proba += SHOP_ADD_SPAWN_PROBA;
-        player.money -= SHOP_PRICE_SPAWN;
+        player.spawn_proba += self.config.shop_add_spawn_proba;
+        player.money -= self.config.shop_price_spawn;
+        player.money_spent += self.config.shop_price_spawn;
         Ok(())
     }
 
-    pub fn delete_rule(&mut self, player: &mut Player, index: usize) -> Result<(), ()> {
-        if player.money < SHOP_PRICE_DELETE_RULE {
-            return Err(());
+    pub fn delete_rule(&mut self, player: &mut Player, index: usize) -> Result<(), ShopError> {
+        if player.money < self.config.shop_price_delete_rule {
+            return Err(ShopError::NotEnoughMoney);
         }
         if index >= player.rules.len() {
-            return Err(());
+            return Err(ShopError::InvalidIndex);
+        }
+        player.remove_rule(index);
+        player.money -= self.config.shop_price_delete_rule;
+        player.money_spent += self.config.shop_price_delete_rule;
+        Ok(())
+    }
+
+    /// Delete one of the opponent's rules, charged to `player`.
+    pub fn delete_opponent_rule(
+        &mut self,
+        player: &mut Player,
+        opponent: &mut Player,
+        index: usize,
+    ) -> Result<(), ShopError> {
+        if player.money < self.config.shop_price_delete_rule {
+            return Err(ShopError::NotEnoughMoney);
         }
-        player.rules.remove(index);
-        player.money -= SHOP_PRICE_DELETE_RULE;
+        if index >= opponent.rules.len() {
+            return Err(ShopError::InvalidIndex);
+        }
+        opponent.remove_rule(index);
+        player.money -= self.config.shop_price_delete_rule;
+        player.money_spent += self.config.shop_price_delete_rule;
         Ok(())
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui, player: &mut Player, opponent: &mut Player) -> bool {
+    /// Show the shop to the current player, `players` being every player of the match. \
+    /// Returns `true` when the player is done shopping.
+    pub fn show(&mut self, ui: &mut egui::Ui, players: &mut [Player]) -> bool {
         let mut finish_clicked = false;
+        let Some(current) = self.current_player() else {
+            return true;
+        };
         components::text::title(ui);
 
-        let player_color = if self.current_player == 1 {
-            COLOR_PLAYER1
-        } else {
-            COLOR_PLAYER2
-        };
+        let player_color = CellState::player(current).color();
         ui.heading(
-            egui::RichText::new(format!("Player {} Shopping", self.current_player))
+            egui::RichText::new(format!("Player {} Shopping", current))
                 .color(player_color)
                 .size(18.0)
                 .strong(),
         );
         ui.add_space(5.0);
-        ui.label(format!("Money: {}", player.money));
+        ui.label(format!("Money: {}", players[current as usize - 1].money));
 
         ui.add_space(10.0);
         ui.separator();
         ui.add_space(10.0);
 
         egui::ScrollArea::vertical().show(ui, |ui| {
+            let player = &mut players[current as usize - 1];
             components::text::heading(ui, "Rules");
 
-            ui.label(format!("Cost: ${}", SHOP_PRICE_RULE));
+            ui.label(format!(
+                "Cost: ${} per neighbourhood covered, wildcards and symmetries cover several. \
+                 Symmetric rules: +${}. Count rules: ${}",
+                self.config.shop_price_rule,
+                self.config.shop_price_symmetry,
+                self.config.shop_price_count_rule
+            ));
             ui.add_space(5.0);
 
-            let can_buy = player.money >= SHOP_PRICE_RULE && player.rules.len() < PLAYER_MAX_RULES;
-            if !can_buy {
-                ui.label("Can't afford or full");
+            let full = player.rules.len() >= player.max_rules;
+            if full {
+                ui.label("Full");
                 ui.add_space(5.0);
             }
 
@@ -113,25 +198,25 @@ impl Shop {
                 .id_salt("shop_rules")
                 .show(ui, |ui| {
                     ui.horizontal(|ui| {
-                        for i in 0..SHOP_NB_RULES {
+                        for i in 0..self.rules.len() {
                             ui.vertical(|ui| {
                                 ui.set_max_width(CELL_SIZE * 3.0 + 10.0);
                                 self.rules[i].show(ui);
                                 ui.add_space(10.0);
 
+                                let price = self.price(i);
+                                let can_buy = !full && player.money >= price;
                                 if self.bought_rules[i] {
                                     ui.label(
                                         egui::RichText::new("bought")
                                             .color(egui::Color32::DARK_GREEN),
                                     );
-                                } else {
-                                    if can_buy {
-                                        if ui.button("Buy").clicked() {
-                                            if self.buy_rule(player, i).is_ok() {
-                                                self.bought_rules[i] = true;
-                                            }
-                                        }
+                                } else if can_buy {
+                                    if ui.button(format!("Buy ${}", price)).clicked() {
+                                        let _ = self.buy_rule(player, i);
                                     }
+                                } else {
+                                    ui.label(format!("${}", price));
                                 }
                             });
                             ui.add_space(5.0);
@@ -145,16 +230,19 @@ impl Shop {
 
             components::text::heading(ui, "Delete Rules");
 
-            ui.label(format!("Cost: ${}", SHOP_PRICE_DELETE_RULE));
+            ui.label(format!("Cost: ${}", self.config.shop_price_delete_rule));
             ui.add_space(5.0);
 
-            let can_delete = player.money >= SHOP_PRICE_DELETE_RULE;
+            let can_delete = player.money >= self.config.shop_price_delete_rule;
 
             if !can_delete {
                 ui.label("Can't afford");
                 ui.add_space(5.0);
             }
 
+            ui.label("Under each rule: cells captured last round / over the match");
+            ui.add_space(5.0);
+
             components::text::heading_small(ui, "Your Rules");
 
             egui::ScrollArea::horizontal()
@@ -166,12 +254,11 @@ impl Shop {
                             ui.vertical(|ui| {
                                 ui.set_max_width(CELL_SIZE * 3.0 + 10.0);
                                 player.rules[i].show(ui);
-                                ui.add_space(10.0);
+                                player.show_rule_stats(ui, i);
+                                ui.add_space(5.0);
 
-                                if can_delete {
-                                    if ui.button("Delete").clicked() {
-                                        indices_to_delete.push(i);
-                                    }
+                                if can_delete && ui.button("Delete").clicked() {
+                                    indices_to_delete.push(i);
                                 }
                             });
                             ui.add_space(5.0);
@@ -183,49 +270,57 @@ impl Shop {
                     }
                 });
 
-            ui.add_space(10.0);
-
-            components::text::heading_small(ui, "Opponent's Rules");
-
-            egui::ScrollArea::horizontal()
-                .id_salt("opponent_rules")
-                .show(ui, |ui| {
-                    let mut indices_to_delete = Vec::new();
-                    ui.horizontal(|ui| {
-                        for i in 0..opponent.rules.len() {
-                            ui.vertical(|ui| {
-                                ui.set_max_width(CELL_SIZE * 3.0 + 10.0);
-                                opponent.rules[i].show(ui);
-                                ui.add_space(10.0);
-
-                                if can_delete {
-                                    if ui.button("Delete").clicked() {
+            let opponents: Vec<u8> = (1..=players.len() as u8)
+                .filter(|p| self.config.side_of(*p) != self.config.side_of(current))
+                .collect();
+            for opponent_id in opponents {
+                ui.add_space(10.0);
+
+                let (player, opponent) = two_mut(players, current, opponent_id);
+                ui.label(
+                    egui::RichText::new(format!("{}'s Rules", opponent.name))
+                        .color(CellState::player(opponent_id).color())
+                        .size(14.0)
+                        .strong(),
+                );
+
+                egui::ScrollArea::horizontal()
+                    .id_salt(("opponent_rules", opponent_id))
+                    .show(ui, |ui| {
+                        let mut indices_to_delete = Vec::new();
+                        ui.horizontal(|ui| {
+                            for i in 0..opponent.rules.len() {
+                                ui.vertical(|ui| {
+                                    ui.set_max_width(CELL_SIZE * 3.0 + 10.0);
+                                    opponent.rules[i].show(ui);
+                                    opponent.show_rule_stats(ui, i);
+                                    ui.add_space(5.0);
+
+                                    if can_delete && ui.button("Delete").clicked() {
                                         indices_to_delete.push(i);
                                     }
-                                }
-                            });
-                            ui.add_space(5.0);
+                                });
+                                ui.add_space(5.0);
+                            }
+                        });
+                        // Delete opponent's rule and charge current player
+                        for i in indices_to_delete.iter().rev() {
+                            let _ = self.delete_opponent_rule(player, opponent, *i);
                         }
                     });
-                    // Delete opponent's rule and charge current player
-                    for i in indices_to_delete.iter().rev() {
-                        if opponent.rules.len() > *i {
-                            opponent.rules.remove(*i);
-                            player.money -= SHOP_PRICE_DELETE_RULE;
-                        }
-                    }
-                });
+            }
 
+            let player = &mut players[current as usize - 1];
             ui.add_space(10.0);
             ui.separator();
             ui.add_space(10.0);
 
             components::text::heading(ui, "Spawn Probability");
 
-            ui.label(format!("Cost: ${}", SHOP_PRICE_SPAWN));
+            ui.label(format!("Cost: ${}", self.config.shop_price_spawn));
             ui.add_space(5.0);
 
-            let can_buy_spawn = player.money >= SHOP_PRICE_SPAWN;
+            let can_buy_spawn = player.money >= self.config.shop_price_spawn;
             if !can_buy_spawn {
                 ui.label("Can't afford");
                 ui.add_space(5.0);
@@ -233,10 +328,8 @@ impl Shop {
 
             ui.label(format!("Current: {:.4}", player.spawn_proba));
 
-            if can_buy_spawn {
-                if ui.button("Upgrade Spawn").clicked() {
-                    let _ = self.buy_spawn(player);
-                }
+            if can_buy_spawn && ui.button("Upgrade Spawn").clicked() {
+                let _ = self.buy_spawn(player);
             }
 
             ui.add_space(10.0);